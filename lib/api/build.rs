@@ -12,4 +12,9 @@ fn main() {
         .file_descriptor_set_path(out_dir.join("users_descriptor.bin"))
         .compile_protos(&["src/users.proto"], &["src"])
         .unwrap();
+
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("admin_descriptor.bin"))
+        .compile_protos(&["src/admin.proto"], &["src"])
+        .unwrap();
 }
@@ -6,3 +6,8 @@ pub const USERS_FILE_DESCRIPTOR_SET: &[u8] =
     tonic::include_file_descriptor_set!("users_descriptor");
 
 tonic::include_proto!("flux.users");
+
+pub const ADMIN_FILE_DESCRIPTOR_SET: &[u8] =
+    tonic::include_file_descriptor_set!("admin_descriptor");
+
+tonic::include_proto!("flux.admin");
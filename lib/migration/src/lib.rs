@@ -5,6 +5,29 @@ mod m20240924_110240_create_user_credentials;
 mod m20240924_110302_create_user_challenges;
 mod m20240928_165536_create_indexes;
 mod m20250601_184245_add_locale_to_users;
+mod m20261019_100000_create_service_clients;
+mod m20261019_110000_create_personal_access_tokens;
+mod m20261019_120000_create_device_codes;
+mod m20261019_130000_create_email_changes;
+mod m20261019_140000_create_users_email_idx;
+mod m20261019_150000_create_user_foreign_keys;
+mod m20261019_160000_create_sessions;
+mod m20261019_170000_add_deleted_at_to_users;
+mod m20261019_180000_create_audit_events;
+mod m20261019_190000_create_data_exports;
+mod m20261019_200000_add_status_to_users;
+mod m20261019_210000_create_roles;
+mod m20261019_220000_add_credentials_write_permission;
+mod m20261019_230000_add_handle_to_users;
+mod m20261019_240000_add_preferred_color_to_users;
+mod m20261019_250000_add_picture_to_users;
+mod m20261019_260000_add_timezone_to_users;
+mod m20261019_270000_create_user_preferences;
+mod m20261019_280000_create_users_search_idx;
+mod m20261019_290000_add_email_search_permission;
+mod m20261019_300000_create_users_created_at_idx;
+mod m20261019_310000_add_users_list_permission;
+mod m20261019_320000_add_actor_client_id_to_audit_events;
 
 pub struct Migrator;
 
@@ -17,6 +40,29 @@ impl MigratorTrait for Migrator {
             Box::new(m20240924_110302_create_user_challenges::Migration),
             Box::new(m20240928_165536_create_indexes::Migration),
             Box::new(m20250601_184245_add_locale_to_users::Migration),
+            Box::new(m20261019_100000_create_service_clients::Migration),
+            Box::new(m20261019_110000_create_personal_access_tokens::Migration),
+            Box::new(m20261019_120000_create_device_codes::Migration),
+            Box::new(m20261019_130000_create_email_changes::Migration),
+            Box::new(m20261019_140000_create_users_email_idx::Migration),
+            Box::new(m20261019_150000_create_user_foreign_keys::Migration),
+            Box::new(m20261019_160000_create_sessions::Migration),
+            Box::new(m20261019_170000_add_deleted_at_to_users::Migration),
+            Box::new(m20261019_180000_create_audit_events::Migration),
+            Box::new(m20261019_190000_create_data_exports::Migration),
+            Box::new(m20261019_200000_add_status_to_users::Migration),
+            Box::new(m20261019_210000_create_roles::Migration),
+            Box::new(m20261019_220000_add_credentials_write_permission::Migration),
+            Box::new(m20261019_230000_add_handle_to_users::Migration),
+            Box::new(m20261019_240000_add_preferred_color_to_users::Migration),
+            Box::new(m20261019_250000_add_picture_to_users::Migration),
+            Box::new(m20261019_260000_add_timezone_to_users::Migration),
+            Box::new(m20261019_270000_create_user_preferences::Migration),
+            Box::new(m20261019_280000_create_users_search_idx::Migration),
+            Box::new(m20261019_290000_add_email_search_permission::Migration),
+            Box::new(m20261019_300000_create_users_created_at_idx::Migration),
+            Box::new(m20261019_310000_add_users_list_permission::Migration),
+            Box::new(m20261019_320000_add_actor_client_id_to_audit_events::Migration),
         ]
     }
 }
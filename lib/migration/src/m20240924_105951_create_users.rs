@@ -33,4 +33,17 @@ pub enum Users {
     FirstName,
     LastName,
     Locale,
+    DeletedAt,
+    PurgedAt,
+    Status,
+    StatusReason,
+    StatusUntil,
+    Handle,
+    HandleKey,
+    HandleChangedAt,
+    PreferredColor,
+    PictureId,
+    PictureUrl,
+    Timezone,
+    CreatedAt,
 }
@@ -11,12 +11,6 @@ impl MigrationTrait for Migration {
                 table_auto(UserCredentials::Table)
                     .col(text(UserCredentials::Id).primary_key())
                     .col(uuid(UserCredentials::UserId))
-                    // .foreign_key(
-                    //     ForeignKey::create()
-                    //         .name("fk_user_credentials_user_id")
-                    //         .from(UserCredentials::Table, UserCredentials::UserId)
-                    //         .to(Users::Table, Users::Id),
-                    // )
                     .col(binary(UserCredentials::PublicKey))
                     .col(integer(UserCredentials::PublicKeyAlgorithm))
                     .to_owned(),
@@ -32,7 +26,7 @@ impl MigrationTrait for Migration {
 }
 
 #[derive(DeriveIden)]
-enum UserCredentials {
+pub enum UserCredentials {
     Table,
     Id,
     UserId,
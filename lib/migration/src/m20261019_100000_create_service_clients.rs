@@ -0,0 +1,35 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(ServiceClients::Table)
+                    .col(text(ServiceClients::Id).primary_key())
+                    .col(text_null(ServiceClients::SecretHash))
+                    .col(text_null(ServiceClients::PublicKey))
+                    .col(text(ServiceClients::Scopes))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ServiceClients::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ServiceClients {
+    Table,
+    Id,
+    SecretHash,
+    PublicKey,
+    Scopes,
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(PersonalAccessTokens::Table)
+                    .col(uuid(PersonalAccessTokens::Id).primary_key())
+                    .col(uuid(PersonalAccessTokens::UserId))
+                    .col(text(PersonalAccessTokens::Name))
+                    .col(text(PersonalAccessTokens::Prefix))
+                    .col(text(PersonalAccessTokens::TokenHash))
+                    .col(text(PersonalAccessTokens::Scopes))
+                    .col(timestamp_null(PersonalAccessTokens::ExpiresAt))
+                    .col(timestamp_null(PersonalAccessTokens::LastUsedAt))
+                    .col(timestamp_null(PersonalAccessTokens::RevokedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("personal_access_tokens_prefix_idx")
+                    .table(PersonalAccessTokens::Table)
+                    .col(PersonalAccessTokens::Prefix)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("personal_access_tokens_user_id_idx")
+                    .table(PersonalAccessTokens::Table)
+                    .col(PersonalAccessTokens::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PersonalAccessTokens::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum PersonalAccessTokens {
+    Table,
+    Id,
+    UserId,
+    Name,
+    Prefix,
+    TokenHash,
+    Scopes,
+    ExpiresAt,
+    LastUsedAt,
+    RevokedAt,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(DeviceCodes::Table)
+                    .col(uuid(DeviceCodes::Id).primary_key())
+                    .col(text(DeviceCodes::DeviceCodeHash))
+                    .col(text(DeviceCodes::UserCode))
+                    .col(text_null(DeviceCodes::ClientName))
+                    .col(text(DeviceCodes::Status))
+                    .col(uuid_null(DeviceCodes::UserId))
+                    .col(big_integer(DeviceCodes::Interval))
+                    .col(timestamp_null(DeviceCodes::LastPolledAt))
+                    .col(timestamp(DeviceCodes::ExpiresAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("device_codes_device_code_hash_idx")
+                    .table(DeviceCodes::Table)
+                    .col(DeviceCodes::DeviceCodeHash)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("device_codes_user_code_idx")
+                    .table(DeviceCodes::Table)
+                    .col(DeviceCodes::UserCode)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DeviceCodes::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum DeviceCodes {
+    Table,
+    Id,
+    DeviceCodeHash,
+    UserCode,
+    ClientName,
+    Status,
+    UserId,
+    Interval,
+    LastPolledAt,
+    ExpiresAt,
+}
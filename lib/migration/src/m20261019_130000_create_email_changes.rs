@@ -0,0 +1,68 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(EmailChanges::Table)
+                    .col(uuid(EmailChanges::Id).primary_key())
+                    .col(uuid(EmailChanges::UserId))
+                    .col(text(EmailChanges::Email))
+                    .col(text(EmailChanges::TokenHash))
+                    .col(timestamp(EmailChanges::ExpiresAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("email_changes_token_hash_idx")
+                    .table(EmailChanges::Table)
+                    .col(EmailChanges::TokenHash)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("email_changes_user_id_idx")
+                    .table(EmailChanges::Table)
+                    .col(EmailChanges::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("email_changes_email_idx")
+                    .table(EmailChanges::Table)
+                    .col(EmailChanges::Email)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EmailChanges::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum EmailChanges {
+    Table,
+    Id,
+    UserId,
+    Email,
+    TokenHash,
+    ExpiresAt,
+}
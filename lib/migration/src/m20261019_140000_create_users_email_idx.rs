@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, sea_orm::Statement};
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Refuse to continue while duplicates exist: merging accounts needs a human decision.
+        let duplicates = db
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                "SELECT lower(email) AS email, string_agg(id::text, ', ') AS user_ids \
+                 FROM users GROUP BY lower(email) HAVING count(*) > 1",
+            ))
+            .await?
+            .iter()
+            .map(|row| -> Result<String, DbErr> {
+                Ok(format!(
+                    "{} ({})",
+                    row.try_get::<String>("", "email")?,
+                    row.try_get::<String>("", "user_ids")?
+                ))
+            })
+            .collect::<Result<Vec<String>, DbErr>>()?;
+
+        if !duplicates.is_empty() {
+            return Err(DbErr::Migration(format!(
+                "users with duplicate emails must be resolved first: {}",
+                duplicates.join("; ")
+            )));
+        }
+
+        manager
+            .drop_index(Index::drop().name("users_id_idx").if_exists().to_owned())
+            .await?;
+
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users (lower(email))",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("users_email_lower_idx").to_owned())
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("users_id_idx")
+                    .table(Users::Table)
+                    .col(Users::Id)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,117 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20240924_105951_create_users::Users,
+    m20240924_110240_create_user_credentials::UserCredentials,
+    m20261019_110000_create_personal_access_tokens::PersonalAccessTokens,
+    m20261019_120000_create_device_codes::DeviceCodes,
+    m20261019_130000_create_email_changes::EmailChanges,
+};
+
+// user_challenges is left without a foreign key: a registration challenge carries the id of a
+// user that is only inserted by `complete`.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for table in [
+            "user_credentials",
+            "personal_access_tokens",
+            "device_codes",
+            "email_changes",
+        ] {
+            db.execute_unprepared(&format!(
+                "DELETE FROM {table} WHERE user_id IS NOT NULL \
+                 AND NOT EXISTS (SELECT 1 FROM users WHERE users.id = {table}.user_id)"
+            ))
+            .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("user_credentials_user_id_idx")
+                    .table(UserCredentials::Table)
+                    .col(UserCredentials::UserId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_user_credentials_user_id")
+                    .from(UserCredentials::Table, UserCredentials::UserId)
+                    .to(Users::Table, Users::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_personal_access_tokens_user_id")
+                    .from(PersonalAccessTokens::Table, PersonalAccessTokens::UserId)
+                    .to(Users::Table, Users::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_device_codes_user_id")
+                    .from(DeviceCodes::Table, DeviceCodes::UserId)
+                    .to(Users::Table, Users::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_email_changes_user_id")
+                    .from(EmailChanges::Table, EmailChanges::UserId)
+                    .to(Users::Table, Users::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (name, table) in [
+            ("fk_email_changes_user_id", EmailChanges::Table.into_iden()),
+            ("fk_device_codes_user_id", DeviceCodes::Table.into_iden()),
+            (
+                "fk_personal_access_tokens_user_id",
+                PersonalAccessTokens::Table.into_iden(),
+            ),
+            (
+                "fk_user_credentials_user_id",
+                UserCredentials::Table.into_iden(),
+            ),
+        ] {
+            manager
+                .drop_foreign_key(ForeignKey::drop().name(name).table(table).to_owned())
+                .await?;
+        }
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("user_credentials_user_id_idx")
+                    .table(UserCredentials::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
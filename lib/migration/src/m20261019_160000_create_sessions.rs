@@ -0,0 +1,56 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(Sessions::Table)
+                    .col(uuid(Sessions::Id).primary_key())
+                    .col(uuid(Sessions::UserId))
+                    .col(text_null(Sessions::Jkt))
+                    .col(timestamp(Sessions::ExpiresAt))
+                    .col(timestamp_null(Sessions::RevokedAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_sessions_user_id")
+                            .from(Sessions::Table, Sessions::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("sessions_user_id_idx")
+                    .table(Sessions::Table)
+                    .col(Sessions::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Sessions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Sessions {
+    Table,
+    Id,
+    UserId,
+    Jkt,
+    ExpiresAt,
+    RevokedAt,
+}
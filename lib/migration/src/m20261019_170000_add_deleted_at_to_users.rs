@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column_if_not_exists(timestamp_null(Users::DeletedAt))
+                    .add_column_if_not_exists(timestamp_null(Users::PurgedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // A deleted account releases its address for a new registration.
+        db.execute_unprepared("DROP INDEX IF EXISTS users_email_lower_idx")
+            .await?;
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX users_email_lower_idx ON users (lower(email)) \
+             WHERE deleted_at IS NULL",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE INDEX users_deleted_at_idx ON users (deleted_at) \
+             WHERE deleted_at IS NOT NULL AND purged_at IS NULL",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DROP INDEX IF EXISTS users_deleted_at_idx")
+            .await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS users_email_lower_idx")
+            .await?;
+        db.execute_unprepared("CREATE UNIQUE INDEX users_email_lower_idx ON users (lower(email))")
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::DeletedAt)
+                    .drop_column(Users::PurgedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
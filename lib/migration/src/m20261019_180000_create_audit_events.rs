@@ -0,0 +1,49 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(AuditEvents::Table)
+                    .col(uuid(AuditEvents::Id).primary_key())
+                    .col(uuid(AuditEvents::UserId))
+                    .col(uuid_null(AuditEvents::ActorId))
+                    .col(text(AuditEvents::Action))
+                    .col(json_binary(AuditEvents::Metadata))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("audit_events_user_id_idx")
+                    .table(AuditEvents::Table)
+                    .col(AuditEvents::UserId)
+                    .col(AuditEvents::Id)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditEvents::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AuditEvents {
+    Table,
+    Id,
+    UserId,
+    ActorId,
+    ActorClientId,
+    Action,
+    Metadata,
+}
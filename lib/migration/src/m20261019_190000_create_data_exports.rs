@@ -0,0 +1,56 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(DataExports::Table)
+                    .col(uuid(DataExports::Id).primary_key())
+                    .col(uuid(DataExports::UserId))
+                    .col(text(DataExports::Status))
+                    .col(json_binary_null(DataExports::Data))
+                    .col(timestamp(DataExports::ExpiresAt))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_data_exports_user_id")
+                            .from(DataExports::Table, DataExports::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("data_exports_user_id_idx")
+                    .table(DataExports::Table)
+                    .col(DataExports::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DataExports::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DataExports {
+    Table,
+    Id,
+    UserId,
+    Status,
+    Data,
+    ExpiresAt,
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column_if_not_exists(text(Users::Status).default("active"))
+                    .add_column_if_not_exists(text_null(Users::StatusReason))
+                    .add_column_if_not_exists(timestamp_null(Users::StatusUntil))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::Status)
+                    .drop_column(Users::StatusReason)
+                    .drop_column(Users::StatusUntil)
+                    .to_owned(),
+            )
+            .await
+    }
+}
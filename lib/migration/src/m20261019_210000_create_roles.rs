@@ -0,0 +1,143 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240924_105951_create_users::Users;
+
+const ROLES: &[(&str, &str, &[&str])] = &[
+    (
+        "admin",
+        "Full access to operator tools",
+        &[
+            "users:lookup",
+            "users:credentials:read",
+            "users:sessions:revoke",
+            "users:status:write",
+            "audit:read",
+            "roles:write",
+        ],
+    ),
+    (
+        "support",
+        "Read-only access for customer support",
+        &["users:lookup", "users:credentials:read", "audit:read"],
+    ),
+];
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(Roles::Table)
+                    .col(text(Roles::Name).primary_key())
+                    .col(text_null(Roles::Description))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                table_auto(RolePermissions::Table)
+                    .col(text(RolePermissions::Role))
+                    .col(text(RolePermissions::Permission))
+                    .primary_key(
+                        Index::create()
+                            .col(RolePermissions::Role)
+                            .col(RolePermissions::Permission),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_role_permissions_role")
+                            .from(RolePermissions::Table, RolePermissions::Role)
+                            .to(Roles::Table, Roles::Name)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                table_auto(UserRoles::Table)
+                    .col(uuid(UserRoles::UserId))
+                    .col(text(UserRoles::Role))
+                    .primary_key(Index::create().col(UserRoles::UserId).col(UserRoles::Role))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_roles_user_id")
+                            .from(UserRoles::Table, UserRoles::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_roles_role")
+                            .from(UserRoles::Table, UserRoles::Role)
+                            .to(Roles::Table, Roles::Name)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        for (name, description, permissions) in ROLES {
+            manager
+                .exec_stmt(
+                    Query::insert()
+                        .into_table(Roles::Table)
+                        .columns([Roles::Name, Roles::Description])
+                        .values_panic([(*name).into(), (*description).into()])
+                        .to_owned(),
+                )
+                .await?;
+
+            let mut insert = Query::insert()
+                .into_table(RolePermissions::Table)
+                .columns([RolePermissions::Role, RolePermissions::Permission])
+                .to_owned();
+            for permission in *permissions {
+                insert.values_panic([(*name).into(), (*permission).into()]);
+            }
+            manager.exec_stmt(insert).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserRoles::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(RolePermissions::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Roles::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Roles {
+    Table,
+    Name,
+    Description,
+}
+
+#[derive(DeriveIden)]
+pub enum RolePermissions {
+    Table,
+    Role,
+    Permission,
+}
+
+#[derive(DeriveIden)]
+enum UserRoles {
+    Table,
+    UserId,
+    Role,
+}
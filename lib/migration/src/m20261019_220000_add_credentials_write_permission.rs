@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20261019_210000_create_roles::RolePermissions;
+
+const PERMISSION: &str = "users:credentials:write";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(RolePermissions::Table)
+                    .columns([RolePermissions::Role, RolePermissions::Permission])
+                    .values_panic(["admin".into(), PERMISSION.into()])
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .exec_stmt(
+                Query::delete()
+                    .from_table(RolePermissions::Table)
+                    .and_where(Expr::col(RolePermissions::Permission).eq(PERMISSION))
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column_if_not_exists(text_null(Users::Handle))
+                    .add_column_if_not_exists(text_null(Users::HandleKey))
+                    .add_column_if_not_exists(timestamp_null(Users::HandleChangedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // handle_key is the folded form of handle, so lookalike handles collide here.
+        manager
+            .create_index(
+                Index::create()
+                    .name("users_handle_key_idx")
+                    .table(Users::Table)
+                    .col(Users::HandleKey)
+                    .unique()
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("users_handle_key_idx").to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::Handle)
+                    .drop_column(Users::HandleKey)
+                    .drop_column(Users::HandleChangedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(UserPreferences::Table)
+                    .col(uuid(UserPreferences::UserId).primary_key())
+                    .col(json_binary(UserPreferences::Data))
+                    .col(big_integer(UserPreferences::Version))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_preferences_user_id")
+                            .from(UserPreferences::Table, UserPreferences::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserPreferences::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserPreferences {
+    Table,
+    UserId,
+    Data,
+    Version,
+}
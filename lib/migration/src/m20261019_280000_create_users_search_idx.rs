@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("CREATE EXTENSION IF NOT EXISTS pg_trgm")
+            .await?;
+
+        // Trigram indexes serve both the LIKE '%...%' filter and the similarity ranking of
+        // SearchUsers, the expressions have to match the ones in the query exactly.
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS users_name_trgm_idx ON users \
+             USING gin (lower(first_name || ' ' || last_name) gin_trgm_ops)",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS users_handle_trgm_idx ON users \
+             USING gin (lower(handle) gin_trgm_ops)",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS users_email_trgm_idx ON users \
+             USING gin (lower(email) gin_trgm_ops)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DROP INDEX IF EXISTS users_email_trgm_idx")
+            .await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS users_handle_trgm_idx")
+            .await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS users_name_trgm_idx")
+            .await?;
+
+        Ok(())
+    }
+}
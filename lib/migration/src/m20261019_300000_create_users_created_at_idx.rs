@@ -0,0 +1,30 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20240924_105951_create_users::Users;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Keyset for ListUsers and ExportUsers.
+        manager
+            .create_index(
+                Index::create()
+                    .name("users_created_at_id_idx")
+                    .table(Users::Table)
+                    .col(Users::CreatedAt)
+                    .col(Users::Id)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("users_created_at_id_idx").to_owned())
+            .await
+    }
+}
@@ -0,0 +1,43 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20261019_180000_create_audit_events::AuditEvents;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // A service client reading across accounts has no account of its own to log under.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditEvents::Table)
+                    .modify_column(uuid_null(AuditEvents::UserId))
+                    .add_column_if_not_exists(text_null(AuditEvents::ActorClientId))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .exec_stmt(
+                Query::delete()
+                    .from_table(AuditEvents::Table)
+                    .and_where(Expr::col(AuditEvents::UserId).is_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditEvents::Table)
+                    .modify_column(uuid(AuditEvents::UserId))
+                    .drop_column(AuditEvents::ActorClientId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
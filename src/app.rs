@@ -5,16 +5,26 @@ use settings::AppSettings;
 use state::AppState;
 use tonic::service::Routes;
 
+mod admin;
+mod audit;
 mod auth;
+mod color;
 mod error;
+mod locale;
+mod mailer;
+mod name;
 mod settings;
 mod state;
+mod storage;
 mod users;
 
 pub async fn run() -> Result<(), Error> {
     let settings = AppSettings::new()?;
     let state = AppState::new(settings).await?;
 
+    auth::purge_job(state.clone());
+    users::watch_job(state.clone());
+
     http(&state).await?;
 
     Ok(())
@@ -25,12 +35,19 @@ async fn http(state: &AppState) -> Result<(), Error> {
         .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
         .register_encoded_file_descriptor_set(flux_users_api::AUTH_FILE_DESCRIPTOR_SET)
         .register_encoded_file_descriptor_set(flux_users_api::USERS_FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(flux_users_api::ADMIN_FILE_DESCRIPTOR_SET)
         .build_v1alpha()?;
 
     let (_, health_service) = tonic_health::server::health_reporter();
 
     let router = Router::new()
-        .nest("/api", Router::new().route("/healthz", get(|| async {})))
+        .nest(
+            "/api",
+            Router::new()
+                .route("/healthz", get(|| async {}))
+                .merge(users::router())
+                .merge(storage::router()),
+        )
         .with_state(state.to_owned());
 
     let routes = Routes::from(router);
@@ -39,6 +56,7 @@ async fn http(state: &AppState) -> Result<(), Error> {
         .add_service(health_service)
         .add_service(auth::auth_service(state.clone()))
         .add_service(users::users_service(state.clone()))
+        .add_service(admin::admin_service(state.clone()))
         .into_axum_router();
 
     let listener = tokio::net::TcpListener::bind(&state.settings.http.endpoint).await?;
@@ -0,0 +1,12 @@
+use flux_users_api::admin_service_server::AdminServiceServer;
+use grpc::GrpcAdminService;
+
+use super::state::AppState;
+
+mod grpc;
+mod service;
+pub(super) mod settings;
+
+pub fn admin_service(state: AppState) -> AdminServiceServer<GrpcAdminService> {
+    AdminServiceServer::new(GrpcAdminService::new(state))
+}
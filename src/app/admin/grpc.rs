@@ -0,0 +1,745 @@
+use std::pin::Pin;
+
+use flux_users_api::{
+    admin_service_server::AdminService, lookup_user_response::User, DeleteUserPasskeyRequest,
+    DeleteUserPasskeyResponse, ExportUsersRequest, ExportUsersResponse, ForceLogoutRequest,
+    ForceLogoutResponse, ListAuditEventsRequest, ListAuditEventsResponse,
+    ListUserCredentialsRequest, ListUserCredentialsResponse, ListUsersRequest, ListUsersResponse,
+    LookupUserRequest, LookupUserResponse, RevokeUserPersonalAccessTokenRequest,
+    RevokeUserPersonalAccessTokenResponse, RevokeUserSessionRequest, RevokeUserSessionResponse,
+    SetUserStatusRequest, SetUserStatusResponse,
+};
+use sea_orm::ActiveEnum as _;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt as _};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::app::{
+    audit::Actor,
+    auth::{
+        principal::{self, permission, scope},
+        repo,
+    },
+    error::AppError,
+    state::AppState,
+};
+
+use super::service;
+
+pub struct GrpcAdminService {
+    pub state: AppState,
+}
+
+impl GrpcAdminService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+}
+
+type ExportUsersStream = Pin<Box<dyn Stream<Item = Result<ExportUsersResponse, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl AdminService for GrpcAdminService {
+    type ExportUsersStream = ExportUsersStream;
+
+    async fn lookup_user(
+        &self,
+        request: Request<LookupUserRequest>,
+    ) -> Result<Response<LookupUserResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::USERS_LOOKUP)
+            .await?;
+
+        let response = lookup_user(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_users(
+        &self,
+        request: Request<ListUsersRequest>,
+    ) -> Result<Response<ListUsersResponse>, Status> {
+        let actor = principal::authenticate(&self.state, &request)
+            .await?
+            .require_scope_or_permission(&self.state.db, scope::USERS_LIST, permission::USERS_LIST)
+            .await?;
+
+        let response = list_users(&self.state, &actor, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn export_users(
+        &self,
+        request: Request<ExportUsersRequest>,
+    ) -> Result<Response<ExportUsersStream>, Status> {
+        let actor = principal::authenticate(&self.state, &request)
+            .await?
+            .require_scope_or_permission(&self.state.db, scope::USERS_LIST, permission::USERS_LIST)
+            .await?;
+
+        let stream = export_users(&self.state, &actor, request.into_inner()).await?;
+
+        Ok(Response::new(stream))
+    }
+
+    async fn list_user_credentials(
+        &self,
+        request: Request<ListUserCredentialsRequest>,
+    ) -> Result<Response<ListUserCredentialsResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::USERS_CREDENTIALS_READ)
+            .await?;
+
+        let response = list_user_credentials(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn delete_user_passkey(
+        &self,
+        request: Request<DeleteUserPasskeyRequest>,
+    ) -> Result<Response<DeleteUserPasskeyResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::USERS_CREDENTIALS_WRITE)
+            .await?;
+
+        let response = delete_user_passkey(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn revoke_user_personal_access_token(
+        &self,
+        request: Request<RevokeUserPersonalAccessTokenRequest>,
+    ) -> Result<Response<RevokeUserPersonalAccessTokenResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::USERS_CREDENTIALS_WRITE)
+            .await?;
+
+        let response =
+            revoke_user_personal_access_token(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn revoke_user_session(
+        &self,
+        request: Request<RevokeUserSessionRequest>,
+    ) -> Result<Response<RevokeUserSessionResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::USERS_SESSIONS_REVOKE)
+            .await?;
+
+        let response = revoke_user_session(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn force_logout(
+        &self,
+        request: Request<ForceLogoutRequest>,
+    ) -> Result<Response<ForceLogoutResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::USERS_SESSIONS_REVOKE)
+            .await?;
+
+        let response = force_logout(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn set_user_status(
+        &self,
+        request: Request<SetUserStatusRequest>,
+    ) -> Result<Response<SetUserStatusResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::USERS_STATUS_WRITE)
+            .await?;
+
+        let response = set_user_status(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_audit_events(
+        &self,
+        request: Request<ListAuditEventsRequest>,
+    ) -> Result<Response<ListAuditEventsResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::AUDIT_READ)
+            .await?;
+
+        let response = list_audit_events(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+}
+
+// Shared by LookupUser, ListUsers and ExportUsers. Only LookupUser reads the roles.
+fn user(user: repo::user::Model, roles: Vec<String>) -> User {
+    User {
+        user_id: Some(user.id.into()),
+        email: Some(user.email),
+        first_name: Some(user.first_name),
+        last_name: Some(user.last_name),
+        locale: user.locale,
+        status: Some(user.status.to_value()),
+        status_reason: user.status_reason,
+        status_until: user.status_until.map(|it| it.and_utc().timestamp()),
+        roles,
+        created_at: Some(user.created_at.and_utc().timestamp()),
+        handle: user.handle,
+        timezone: user.timezone,
+    }
+}
+
+fn parse_id(field: &'static str, value: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(value).map_err(|_| {
+        let mut errors = ValidationErrors::new();
+        errors.add(field, ValidationError::new("uuid"));
+        errors.into()
+    })
+}
+
+async fn lookup_user(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: LookupUserRequest,
+) -> Result<LookupUserResponse, AppError> {
+    let response = service::lookup_user(db, actor_id, request.try_into()?).await?;
+
+    Ok(response.into())
+}
+
+mod lookup_user {
+    use flux_users_api::{LookupUserRequest, LookupUserResponse};
+    use validator::{ValidationError, ValidationErrors};
+
+    use crate::app::{
+        admin::service::lookup_user::{Request, Response},
+        error::AppError,
+    };
+
+    use super::parse_id;
+
+    impl TryFrom<LookupUserRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: LookupUserRequest) -> Result<Self, Self::Error> {
+            match (request.user_id, request.email) {
+                (Some(user_id), None) => Ok(Self::Id(parse_id("user_id", &user_id)?)),
+                (None, Some(email)) => Ok(Self::Email(email.trim().into())),
+                _ => {
+                    let mut errors = ValidationErrors::new();
+                    errors.add("user_id", ValidationError::new("exactly_one"));
+                    Err(errors.into())
+                }
+            }
+        }
+    }
+
+    impl From<Response> for LookupUserResponse {
+        fn from(Response { user, roles }: Response) -> Self {
+            Self {
+                user: Some(super::user(user, roles)),
+            }
+        }
+    }
+}
+
+async fn list_users(
+    AppState { db, settings, .. }: &AppState,
+    actor: &Actor,
+    request: ListUsersRequest,
+) -> Result<ListUsersResponse, AppError> {
+    let response =
+        service::list_users(db, &settings.users.list, actor, request.try_into()?).await?;
+
+    Ok(ListUsersResponse {
+        users: response
+            .users
+            .into_iter()
+            .map(|it| user(it, vec![]))
+            .collect(),
+        next_page_token: response.next_page_token,
+    })
+}
+
+async fn export_users(
+    AppState { db, settings, .. }: &AppState,
+    actor: &Actor,
+    request: ExportUsersRequest,
+) -> Result<ExportUsersStream, AppError> {
+    let filter = request.filter.unwrap_or_default().try_into()?;
+    let receiver = service::export_users(db, &settings.users.list, actor, filter).await?;
+
+    Ok(Box::pin(ReceiverStream::new(receiver).map(|it| {
+        Ok(ExportUsersResponse {
+            user: Some(user(it?, vec![])),
+        })
+    })))
+}
+
+mod list_users {
+    use chrono::{DateTime, NaiveDateTime};
+    use flux_users_api::{ListUsersRequest, UserFilter};
+    use sea_orm::ActiveEnum as _;
+    use validator::{Validate as _, ValidationError, ValidationErrors};
+
+    use crate::app::{
+        admin::service::list_users::{Filter, Request},
+        auth::repo::user::Status,
+        error::AppError,
+        locale,
+    };
+
+    impl TryFrom<ListUsersRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: ListUsersRequest) -> Result<Self, Self::Error> {
+            Ok(Self {
+                page_size: request.page_size(),
+                page_token: request.page_token,
+                filter: request.filter.unwrap_or_default().try_into()?,
+            })
+        }
+    }
+
+    impl TryFrom<UserFilter> for Filter {
+        type Error = AppError;
+
+        fn try_from(filter: UserFilter) -> Result<Self, Self::Error> {
+            let mut errors = ValidationErrors::new();
+
+            let mut timestamp =
+                |field: &'static str, value: Option<i64>| -> Option<NaiveDateTime> {
+                    let value = value?;
+                    let timestamp = DateTime::from_timestamp(value, 0).map(|it| it.naive_utc());
+                    if timestamp.is_none() {
+                        errors.add(field, ValidationError::new("timestamp"));
+                    }
+                    timestamp
+                };
+            let created_after = timestamp("created_after", filter.created_after);
+            let created_before = timestamp("created_before", filter.created_before);
+
+            let locale = filter
+                .locale
+                .filter(|it| !it.trim().is_empty())
+                .and_then(|it| {
+                    let locale = locale::canonicalize(&it);
+                    if locale.is_none() {
+                        errors.add("locale", ValidationError::new("locale"));
+                    }
+                    locale
+                });
+
+            let status = filter.status.filter(|it| !it.is_empty()).and_then(|it| {
+                let status = Status::try_from_value(&it).ok();
+                if status.is_none() {
+                    errors.add("status", ValidationError::new("status"));
+                }
+                status
+            });
+
+            if !errors.is_empty() {
+                return Err(errors.into());
+            }
+
+            let data = Self {
+                created_after,
+                created_before,
+                locale,
+                status,
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::UserFilter;
+
+        use crate::app::{admin::service::list_users::Filter, error::AppError};
+
+        fn field_error(filter: UserFilter, field: &str) -> bool {
+            let result: Result<Filter, AppError> = filter.try_into();
+            matches!(result, Err(AppError::Validation(it)) if it.field_errors().contains_key(field))
+        }
+
+        #[test]
+        fn should_reject_out_of_range_timestamps() {
+            assert!(field_error(
+                UserFilter {
+                    created_after: Some(i64::MAX),
+                    ..Default::default()
+                },
+                "created_after"
+            ));
+            assert!(field_error(
+                UserFilter {
+                    created_before: Some(i64::MIN),
+                    ..Default::default()
+                },
+                "created_before"
+            ));
+        }
+
+        #[test]
+        fn should_reject_unknown_locales() {
+            assert!(field_error(
+                UserFilter {
+                    locale: Some("not a locale".into()),
+                    ..Default::default()
+                },
+                "locale"
+            ));
+        }
+
+        #[test]
+        fn should_reject_unknown_statuses() {
+            assert!(field_error(
+                UserFilter {
+                    status: Some("paused".into()),
+                    ..Default::default()
+                },
+                "status"
+            ));
+        }
+
+        #[test]
+        fn should_accept_empty_filters() {
+            let result: Result<Filter, AppError> = UserFilter {
+                locale: Some(" ".into()),
+                status: Some("".into()),
+                ..Default::default()
+            }
+            .try_into();
+
+            assert!(matches!(result, Ok(it) if it == Filter::default()));
+        }
+    }
+}
+
+async fn list_user_credentials(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: ListUserCredentialsRequest,
+) -> Result<ListUserCredentialsResponse, AppError> {
+    let user_id = parse_id("user_id", request.user_id())?;
+    let response = service::list_user_credentials(db, actor_id, user_id).await?;
+
+    Ok(response.into())
+}
+
+mod list_user_credentials {
+    use flux_users_api::{
+        list_user_credentials_response::{Passkey, PersonalAccessToken, Session},
+        ListUserCredentialsResponse,
+    };
+
+    use crate::app::{admin::service::list_user_credentials::Response, auth::repo};
+
+    impl From<Response> for ListUserCredentialsResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                passkeys: res.passkeys.into_iter().map(Into::into).collect(),
+                personal_access_tokens: res
+                    .personal_access_tokens
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+                sessions: res.sessions.into_iter().map(Into::into).collect(),
+            }
+        }
+    }
+
+    impl From<repo::user_credential::Model> for Passkey {
+        fn from(model: repo::user_credential::Model) -> Self {
+            Self {
+                passkey_id: Some(model.id),
+                created_at: Some(model.created_at.and_utc().timestamp()),
+            }
+        }
+    }
+
+    impl From<repo::personal_access_token::Model> for PersonalAccessToken {
+        fn from(model: repo::personal_access_token::Model) -> Self {
+            Self {
+                personal_access_token_id: Some(model.id.into()),
+                scopes: model.scopes(),
+                name: Some(model.name),
+                prefix: Some(model.prefix),
+                created_at: Some(model.created_at.and_utc().timestamp()),
+                expires_at: model.expires_at.map(|it| it.and_utc().timestamp()),
+                last_used_at: model.last_used_at.map(|it| it.and_utc().timestamp()),
+                revoked_at: model.revoked_at.map(|it| it.and_utc().timestamp()),
+            }
+        }
+    }
+
+    impl From<repo::session::Model> for Session {
+        fn from(model: repo::session::Model) -> Self {
+            Self {
+                session_id: Some(model.id.into()),
+                sender_constrained: Some(model.jkt.is_some()),
+                created_at: Some(model.created_at.and_utc().timestamp()),
+                expires_at: Some(model.expires_at.and_utc().timestamp()),
+                revoked_at: model.revoked_at.map(|it| it.and_utc().timestamp()),
+            }
+        }
+    }
+}
+
+async fn delete_user_passkey(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: DeleteUserPasskeyRequest,
+) -> Result<DeleteUserPasskeyResponse, AppError> {
+    service::delete_user_passkey(db, actor_id, request.try_into()?).await?;
+
+    Ok(DeleteUserPasskeyResponse {})
+}
+
+mod delete_user_passkey {
+    use flux_users_api::DeleteUserPasskeyRequest;
+
+    use crate::app::{admin::service::delete_user_passkey::Request, error::AppError};
+
+    use super::parse_id;
+
+    impl TryFrom<DeleteUserPasskeyRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: DeleteUserPasskeyRequest) -> Result<Self, Self::Error> {
+            Ok(Self {
+                user_id: parse_id("user_id", request.user_id())?,
+                passkey_id: request.passkey_id().into(),
+            })
+        }
+    }
+}
+
+async fn revoke_user_personal_access_token(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: RevokeUserPersonalAccessTokenRequest,
+) -> Result<RevokeUserPersonalAccessTokenResponse, AppError> {
+    service::revoke_user_personal_access_token(db, actor_id, request.try_into()?).await?;
+
+    Ok(RevokeUserPersonalAccessTokenResponse {})
+}
+
+mod revoke_user_personal_access_token {
+    use flux_users_api::RevokeUserPersonalAccessTokenRequest;
+
+    use crate::app::{admin::service::revoke_user_personal_access_token::Request, error::AppError};
+
+    use super::parse_id;
+
+    impl TryFrom<RevokeUserPersonalAccessTokenRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: RevokeUserPersonalAccessTokenRequest) -> Result<Self, Self::Error> {
+            Ok(Self {
+                user_id: parse_id("user_id", request.user_id())?,
+                id: parse_id(
+                    "personal_access_token_id",
+                    request.personal_access_token_id(),
+                )?,
+            })
+        }
+    }
+}
+
+async fn revoke_user_session(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: RevokeUserSessionRequest,
+) -> Result<RevokeUserSessionResponse, AppError> {
+    service::revoke_user_session(db, actor_id, request.try_into()?).await?;
+
+    Ok(RevokeUserSessionResponse {})
+}
+
+mod revoke_user_session {
+    use flux_users_api::RevokeUserSessionRequest;
+
+    use crate::app::{admin::service::revoke_user_session::Request, error::AppError};
+
+    use super::parse_id;
+
+    impl TryFrom<RevokeUserSessionRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: RevokeUserSessionRequest) -> Result<Self, Self::Error> {
+            Ok(Self {
+                user_id: parse_id("user_id", request.user_id())?,
+                id: parse_id("session_id", request.session_id())?,
+            })
+        }
+    }
+}
+
+async fn force_logout(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: ForceLogoutRequest,
+) -> Result<ForceLogoutResponse, AppError> {
+    let user_id = parse_id("user_id", request.user_id())?;
+    service::force_logout(db, actor_id, user_id).await?;
+
+    Ok(ForceLogoutResponse {})
+}
+
+async fn set_user_status(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: SetUserStatusRequest,
+) -> Result<SetUserStatusResponse, AppError> {
+    service::set_user_status(db, actor_id, request.try_into()?).await?;
+
+    Ok(SetUserStatusResponse {})
+}
+
+mod set_user_status {
+    use chrono::DateTime;
+    use flux_users_api::SetUserStatusRequest;
+    use sea_orm::ActiveEnum as _;
+    use validator::{Validate as _, ValidationError, ValidationErrors};
+
+    use crate::app::{
+        admin::service::set_user_status::Request, auth::repo::user::Status, error::AppError,
+    };
+
+    use super::parse_id;
+
+    impl TryFrom<SetUserStatusRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: SetUserStatusRequest) -> Result<Self, Self::Error> {
+            let status = Status::try_from_value(&request.status().to_string()).map_err(|_| {
+                let mut errors = ValidationErrors::new();
+                errors.add("status", ValidationError::new("status"));
+                errors
+            })?;
+
+            let until = request
+                .until
+                .map(|until| {
+                    DateTime::from_timestamp(until, 0)
+                        .map(|it| it.naive_utc())
+                        .ok_or_else(|| {
+                            let mut errors = ValidationErrors::new();
+                            errors.add("until", ValidationError::new("timestamp"));
+                            errors
+                        })
+                })
+                .transpose()?;
+
+            let data = Self {
+                user_id: parse_id("user_id", request.user_id())?,
+                status,
+                reason: request
+                    .reason
+                    .map(|it| it.trim().to_string())
+                    .filter(|it| !it.is_empty()),
+                until,
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::SetUserStatusRequest;
+        use uuid::Uuid;
+
+        use crate::app::{admin::service::set_user_status::Request, error::AppError};
+
+        #[test]
+        fn should_reject_out_of_range_until() {
+            let result: Result<Request, AppError> = SetUserStatusRequest {
+                user_id: Some(Uuid::now_v7().to_string()),
+                status: Some("suspended".into()),
+                reason: None,
+                until: Some(i64::MAX),
+            }
+            .try_into();
+
+            assert!(matches!(
+                result,
+                Err(AppError::Validation(it)) if it.field_errors().contains_key("until")
+            ));
+        }
+    }
+}
+
+async fn list_audit_events(
+    AppState { db, settings, .. }: &AppState,
+    actor_id: Uuid,
+    request: ListAuditEventsRequest,
+) -> Result<ListAuditEventsResponse, AppError> {
+    let response = service::list_audit_events(
+        db,
+        &settings.admin.audit_events,
+        actor_id,
+        request.try_into()?,
+    )
+    .await?;
+
+    Ok(ListAuditEventsResponse {
+        audit_events: response.audit_events.into_iter().map(Into::into).collect(),
+        next_page_token: response.next_page_token,
+    })
+}
+
+mod list_audit_events {
+    use flux_users_api::{list_audit_events_response::AuditEvent, ListAuditEventsRequest};
+
+    use crate::app::{
+        admin::service::list_audit_events::Request, audit::repo::audit_event, error::AppError,
+    };
+
+    use super::parse_id;
+
+    impl TryFrom<ListAuditEventsRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: ListAuditEventsRequest) -> Result<Self, Self::Error> {
+            Ok(Self {
+                user_id: parse_id("user_id", request.user_id())?,
+                page_size: request.page_size(),
+                page_token: request.page_token,
+            })
+        }
+    }
+
+    impl From<audit_event::Model> for AuditEvent {
+        fn from(model: audit_event::Model) -> Self {
+            Self {
+                audit_event_id: Some(model.id.into()),
+                user_id: model.user_id.map(Into::into),
+                actor_id: model.actor_id.map(Into::into),
+                actor_client_id: model.actor_client_id,
+                action: Some(model.action),
+                metadata: Some(model.metadata.to_string()),
+                created_at: Some(model.created_at.and_utc().timestamp()),
+            }
+        }
+    }
+}
@@ -0,0 +1,665 @@
+use chrono::Utc;
+use sea_orm::{ActiveEnum as _, DbConn, Set, TransactionTrait as _};
+use serde_json::json;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::app::{
+    audit::{self, action},
+    auth::repo,
+    error::AppError,
+    users::{self, cursor, settings::ListSettings},
+};
+
+use super::settings::AuditEventsSettings;
+
+pub async fn lookup_user(
+    db: &DbConn,
+    actor_id: Uuid,
+    req: lookup_user::Request,
+) -> Result<lookup_user::Response, AppError> {
+    let user = match &req {
+        lookup_user::Request::Id(id) => repo::find_user_by_id(db, *id).await?,
+        lookup_user::Request::Email(email) => repo::find_user_by_email(db, email).await?,
+    }
+    .ok_or(AppError::NotFound)?;
+
+    let roles = repo::find_roles_by_user_id(db, user.id).await?;
+
+    audit::record_as(
+        db,
+        actor_id,
+        user.id,
+        action::ADMIN_USER_LOOKED_UP,
+        json!({}),
+    )
+    .await?;
+
+    Ok(lookup_user::Response { user, roles })
+}
+
+pub mod lookup_user {
+    use uuid::Uuid;
+
+    use crate::app::auth::repo;
+
+    pub enum Request {
+        Id(Uuid),
+        Email(String),
+    }
+
+    pub struct Response {
+        pub user: repo::user::Model,
+        pub roles: Vec<String>,
+    }
+}
+
+// Oldest first. The page token is bound to the filter, resuming with a different one would
+// silently skip users.
+pub async fn list_users(
+    db: &DbConn,
+    settings: &ListSettings,
+    actor: &audit::Actor,
+    req: list_users::Request,
+) -> Result<list_users::Response, AppError> {
+    use list_users::{Cursor, Response};
+
+    let after = match req.page_token.as_deref().filter(|it| !it.is_empty()) {
+        Some(token) => {
+            let cursor: Cursor = cursor::decode(token)?;
+            if cursor.filter != req.filter {
+                return Err(cursor::invalid().into());
+            }
+            Some((cursor.created_at, cursor.id))
+        }
+        None => None,
+    };
+
+    let page_size = cursor::page_size(
+        req.page_size,
+        settings.default_page_size,
+        settings.max_page_size,
+    );
+
+    let mut users = repo::find_users_page(
+        db,
+        &(&req.filter).into(),
+        after,
+        page_size + 1,
+        Utc::now().naive_utc(),
+    )
+    .await?;
+
+    let next_page_token = cursor::next_page(&mut users, page_size, |user| Cursor {
+        filter: req.filter.clone(),
+        created_at: user.created_at,
+        id: user.id,
+    })?;
+
+    audit::record_by(
+        db,
+        actor,
+        action::ADMIN_USERS_LISTED,
+        json!({ "filter": req.filter, "count": users.len() }),
+    )
+    .await?;
+
+    Ok(Response {
+        users,
+        next_page_token,
+    })
+}
+
+// Pages through every matching user in batches. Each batch is its own query, so users created
+// meanwhile are included once the export reaches them, and nobody is sent twice.
+pub async fn export_users(
+    db: &DbConn,
+    settings: &ListSettings,
+    actor: &audit::Actor,
+    filter: list_users::Filter,
+) -> Result<mpsc::Receiver<Result<repo::user::Model, AppError>>, AppError> {
+    audit::record_by(
+        db,
+        actor,
+        action::ADMIN_USERS_EXPORTED,
+        json!({ "filter": filter }),
+    )
+    .await?;
+
+    let db = db.clone();
+    let batch_size = settings.export_batch_size.max(1);
+    let (sender, receiver) = mpsc::channel(batch_size as usize);
+
+    tokio::spawn(async move {
+        let filter = repo::UserFilter::from(&filter);
+        let mut after = None;
+
+        loop {
+            let users = match repo::find_users_page(
+                &db,
+                &filter,
+                after,
+                batch_size,
+                Utc::now().naive_utc(),
+            )
+            .await
+            {
+                Ok(users) => users,
+                Err(err) => {
+                    let _ = sender.send(Err(err.into())).await;
+                    return;
+                }
+            };
+
+            let last = users.last().map(|user| (user.created_at, user.id));
+            let done = (users.len() as u64) < batch_size;
+
+            for user in users {
+                if sender.send(Ok(user)).await.is_err() {
+                    return;
+                }
+            }
+
+            match last {
+                Some(last) if !done => after = Some(last),
+                _ => return,
+            }
+        }
+    });
+
+    Ok(receiver)
+}
+
+pub mod list_users {
+    use chrono::NaiveDateTime;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+    use validator::{Validate, ValidationError};
+
+    use crate::app::auth::repo;
+
+    #[derive(Validate, Serialize, Deserialize, Clone, Default, PartialEq)]
+    #[validate(schema(function = "validate_range"))]
+    pub struct Filter {
+        pub created_after: Option<NaiveDateTime>,
+        pub created_before: Option<NaiveDateTime>,
+        // Canonical BCP 47 tag, matched exactly.
+        pub locale: Option<String>,
+        pub status: Option<repo::user::Status>,
+    }
+
+    fn validate_range(filter: &Filter) -> Result<(), ValidationError> {
+        match (filter.created_after, filter.created_before) {
+            (Some(after), Some(before)) if after >= before => {
+                Err(ValidationError::new("created_range"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    impl From<&Filter> for repo::UserFilter {
+        fn from(filter: &Filter) -> Self {
+            Self {
+                created_after: filter.created_after,
+                created_before: filter.created_before,
+                locale: filter.locale.clone(),
+                status: filter.status.clone(),
+            }
+        }
+    }
+
+    pub struct Request {
+        pub filter: Filter,
+        pub page_size: u64,
+        pub page_token: Option<String>,
+    }
+
+    pub struct Response {
+        pub users: Vec<repo::user::Model>,
+        pub next_page_token: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Cursor {
+        pub filter: Filter,
+        pub created_at: NaiveDateTime,
+        pub id: Uuid,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use chrono::{Duration, Utc};
+        use uuid::Uuid;
+        use validator::Validate as _;
+
+        use crate::app::{audit::Actor, auth::repo::user::Status, state::AppState, users::cursor};
+
+        use super::{super::list_users, Cursor, Filter, Request};
+
+        #[test]
+        fn should_reject_empty_created_range() {
+            let now = Utc::now().naive_utc();
+            let filter = |after: Duration| Filter {
+                created_after: Some(now + after),
+                created_before: Some(now),
+                ..Default::default()
+            };
+
+            assert!(filter(Duration::days(-1)).validate().is_ok());
+            assert!(filter(Duration::zero()).validate().is_err());
+            assert!(Filter::default().validate().is_ok());
+        }
+
+        #[tokio::test]
+        async fn should_reject_token_for_another_filter() {
+            let settings = &AppState::default().settings.users.list;
+            let cursor = Cursor {
+                filter: Filter {
+                    status: Some(Status::Banned),
+                    ..Default::default()
+                },
+                created_at: Utc::now().naive_utc(),
+                id: Uuid::now_v7(),
+            };
+
+            cursor::assert_rejects_token(&cursor, |db, page_token| async move {
+                list_users(
+                    &db,
+                    settings,
+                    &Actor::User(Uuid::now_v7()),
+                    Request {
+                        page_size: 0,
+                        page_token: Some(page_token),
+                        filter: Filter::default(),
+                    },
+                )
+                .await
+            })
+            .await;
+        }
+    }
+}
+
+pub async fn list_user_credentials(
+    db: &DbConn,
+    actor_id: Uuid,
+    user_id: Uuid,
+) -> Result<list_user_credentials::Response, AppError> {
+    repo::find_user_by_id(db, user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let response = list_user_credentials::Response {
+        passkeys: repo::find_user_credentials_by_user_id(db, user_id).await?,
+        personal_access_tokens: repo::find_personal_access_tokens_by_user_id(db, user_id).await?,
+        sessions: repo::find_sessions_by_user_id(db, user_id).await?,
+    };
+
+    audit::record_as(
+        db,
+        actor_id,
+        user_id,
+        action::ADMIN_CREDENTIALS_LISTED,
+        json!({}),
+    )
+    .await?;
+
+    Ok(response)
+}
+
+pub mod list_user_credentials {
+    use crate::app::auth::repo;
+
+    pub struct Response {
+        pub passkeys: Vec<repo::user_credential::Model>,
+        pub personal_access_tokens: Vec<repo::personal_access_token::Model>,
+        pub sessions: Vec<repo::session::Model>,
+    }
+}
+
+pub async fn delete_user_passkey(
+    db: &DbConn,
+    actor_id: Uuid,
+    req: delete_user_passkey::Request,
+) -> Result<(), AppError> {
+    let txn = db.begin().await?;
+
+    if !repo::delete_user_credential(&txn, &req.passkey_id, req.user_id).await? {
+        return Err(AppError::NotFound);
+    }
+
+    audit::record_as(
+        &txn,
+        actor_id,
+        req.user_id,
+        action::ADMIN_PASSKEY_DELETED,
+        json!({ "passkey_id": req.passkey_id }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod delete_user_passkey {
+    use uuid::Uuid;
+
+    pub struct Request {
+        pub user_id: Uuid,
+        pub passkey_id: String,
+    }
+}
+
+pub async fn revoke_user_personal_access_token(
+    db: &DbConn,
+    actor_id: Uuid,
+    req: revoke_user_personal_access_token::Request,
+) -> Result<(), AppError> {
+    let txn = db.begin().await?;
+
+    let revoked =
+        repo::revoke_personal_access_token(&txn, req.id, req.user_id, Utc::now().naive_utc())
+            .await?;
+
+    if !revoked {
+        return Err(AppError::NotFound);
+    }
+
+    audit::record_as(
+        &txn,
+        actor_id,
+        req.user_id,
+        action::ADMIN_PERSONAL_ACCESS_TOKEN_REVOKED,
+        json!({ "personal_access_token_id": req.id }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod revoke_user_personal_access_token {
+    use uuid::Uuid;
+
+    pub struct Request {
+        pub user_id: Uuid,
+        pub id: Uuid,
+    }
+}
+
+pub async fn revoke_user_session(
+    db: &DbConn,
+    actor_id: Uuid,
+    req: revoke_user_session::Request,
+) -> Result<(), AppError> {
+    let txn = db.begin().await?;
+
+    if !repo::revoke_session(&txn, req.id, req.user_id, Utc::now().naive_utc()).await? {
+        return Err(AppError::NotFound);
+    }
+
+    audit::record_as(
+        &txn,
+        actor_id,
+        req.user_id,
+        action::ADMIN_SESSION_REVOKED,
+        json!({ "session_id": req.id }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod revoke_user_session {
+    use uuid::Uuid;
+
+    pub struct Request {
+        pub user_id: Uuid,
+        pub id: Uuid,
+    }
+}
+
+pub async fn force_logout(db: &DbConn, actor_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    repo::find_user_by_id(&txn, user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    repo::revoke_sessions_by_user_id(&txn, user_id, now).await?;
+    repo::revoke_personal_access_tokens_by_user_id(&txn, user_id, now).await?;
+
+    audit::record_as(
+        &txn,
+        actor_id,
+        user_id,
+        action::ADMIN_FORCED_LOGOUT,
+        json!({}),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub async fn set_user_status(
+    db: &DbConn,
+    actor_id: Uuid,
+    req: set_user_status::Request,
+) -> Result<(), AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let user = repo::find_user_by_id_with_lock(&txn, req.user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let previous = user.status_at(now);
+
+    let mut user: repo::user::ActiveModel = user.into();
+    user.status = Set(req.status.clone());
+    user.status_reason = Set(req.reason.clone());
+    user.status_until = Set(req.until);
+    user.updated_at = Set(now);
+    repo::update_user(&txn, user).await?;
+    users::watch::notify(&txn, req.user_id).await?;
+
+    audit::record_as(
+        &txn,
+        actor_id,
+        req.user_id,
+        action::ADMIN_STATUS_CHANGED,
+        json!({
+            "from": previous.to_value(),
+            "to": req.status.to_value(),
+            "reason": req.reason,
+            "until": req.until,
+        }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod set_user_status {
+    use chrono::{NaiveDateTime, Utc};
+    use uuid::Uuid;
+    use validator::{Validate, ValidationError};
+
+    use crate::app::auth::repo::user::Status;
+
+    #[derive(Validate)]
+    #[validate(schema(function = "validate_until"))]
+    pub struct Request {
+        pub user_id: Uuid,
+        pub status: Status,
+        #[validate(length(min = 1, max = 500))]
+        pub reason: Option<String>,
+        pub until: Option<NaiveDateTime>,
+    }
+
+    // Only suspensions expire; an active or banned status has no end date.
+    fn validate_until(req: &Request) -> Result<(), ValidationError> {
+        match (&req.status, req.until) {
+            (_, None) => Ok(()),
+            (Status::Suspended, Some(until)) if until > Utc::now().naive_utc() => Ok(()),
+            _ => Err(ValidationError::new("until")),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use chrono::{Duration, Utc};
+        use uuid::Uuid;
+        use validator::Validate as _;
+
+        use super::{Request, Status};
+
+        fn request(status: Status, until: Option<Duration>) -> Request {
+            Request {
+                user_id: Uuid::now_v7(),
+                status,
+                reason: None,
+                until: until.map(|it| Utc::now().naive_utc() + it),
+            }
+        }
+
+        #[test]
+        fn should_accept_timed_suspension() {
+            assert!(request(Status::Suspended, Some(Duration::days(1)))
+                .validate()
+                .is_ok());
+            assert!(request(Status::Suspended, None).validate().is_ok());
+            assert!(request(Status::Banned, None).validate().is_ok());
+        }
+
+        #[test]
+        fn should_reject_until_outside_suspension() {
+            assert!(request(Status::Suspended, Some(Duration::days(-1)))
+                .validate()
+                .is_err());
+            assert!(request(Status::Banned, Some(Duration::days(1)))
+                .validate()
+                .is_err());
+            assert!(request(Status::Active, Some(Duration::days(1)))
+                .validate()
+                .is_err());
+        }
+    }
+}
+
+pub async fn list_audit_events(
+    db: &DbConn,
+    settings: &AuditEventsSettings,
+    actor_id: Uuid,
+    req: list_audit_events::Request,
+) -> Result<list_audit_events::Response, AppError> {
+    use list_audit_events::{Cursor, Response};
+
+    let after = match req.page_token.as_deref().filter(|it| !it.is_empty()) {
+        Some(token) => {
+            let cursor: Cursor = cursor::decode(token)?;
+            if cursor.user_id != req.user_id {
+                return Err(cursor::invalid().into());
+            }
+            Some(cursor.id)
+        }
+        None => None,
+    };
+
+    let page_size = cursor::page_size(
+        req.page_size,
+        settings.default_page_size,
+        settings.max_page_size,
+    );
+
+    repo::find_user_by_id(db, req.user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let mut audit_events =
+        audit::repo::find_audit_events_page(db, req.user_id, after, page_size + 1).await?;
+
+    let next_page_token = cursor::next_page(&mut audit_events, page_size, |audit_event| Cursor {
+        user_id: req.user_id,
+        id: audit_event.id,
+    })?;
+
+    audit::record_as(
+        db,
+        actor_id,
+        req.user_id,
+        action::ADMIN_AUDIT_LOG_VIEWED,
+        json!({}),
+    )
+    .await?;
+
+    Ok(Response {
+        audit_events,
+        next_page_token,
+    })
+}
+
+pub mod list_audit_events {
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::app::audit;
+
+    pub struct Request {
+        pub user_id: Uuid,
+        pub page_size: u64,
+        pub page_token: Option<String>,
+    }
+
+    pub struct Response {
+        pub audit_events: Vec<audit::repo::audit_event::Model>,
+        pub next_page_token: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Cursor {
+        pub user_id: Uuid,
+        pub id: Uuid,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use uuid::Uuid;
+
+        use crate::app::{state::AppState, users::cursor};
+
+        use super::{super::list_audit_events, Cursor, Request};
+
+        #[tokio::test]
+        async fn should_reject_token_for_another_user() {
+            let settings = &AppState::default().settings.admin.audit_events;
+            let cursor = Cursor {
+                user_id: Uuid::now_v7(),
+                id: Uuid::now_v7(),
+            };
+
+            cursor::assert_rejects_token(&cursor, |db, page_token| async move {
+                list_audit_events(
+                    &db,
+                    settings,
+                    Uuid::now_v7(),
+                    Request {
+                        user_id: Uuid::now_v7(),
+                        page_size: 0,
+                        page_token: Some(page_token),
+                    },
+                )
+                .await
+            })
+            .await;
+        }
+    }
+}
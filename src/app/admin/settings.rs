@@ -0,0 +1,12 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct AdminSettings {
+    pub audit_events: AuditEventsSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AuditEventsSettings {
+    pub default_page_size: u64,
+    pub max_page_size: u64,
+}
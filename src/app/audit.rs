@@ -0,0 +1,112 @@
+use chrono::Utc;
+use sea_orm::{ConnectionTrait, DbErr};
+use serde_json::Value;
+use uuid::Uuid;
+
+pub mod repo;
+
+pub mod action {
+    pub const USER_CREATED: &str = "user.created";
+    pub const SESSION_CREATED: &str = "session.created";
+    pub const PROFILE_UPDATED: &str = "profile.updated";
+    pub const HANDLE_CLAIMED: &str = "handle.claimed";
+    pub const HANDLE_CHANGED: &str = "handle.changed";
+    pub const AVATAR_UPLOADED: &str = "avatar.uploaded";
+    pub const AVATAR_DELETED: &str = "avatar.deleted";
+    pub const EMAIL_CHANGE_REQUESTED: &str = "email.change_requested";
+    pub const EMAIL_CHANGED: &str = "email.changed";
+    pub const DEVICE_APPROVED: &str = "device.approved";
+    pub const DEVICE_DENIED: &str = "device.denied";
+    pub const PERSONAL_ACCESS_TOKEN_CREATED: &str = "personal_access_token.created";
+    pub const PERSONAL_ACCESS_TOKEN_REVOKED: &str = "personal_access_token.revoked";
+    pub const DATA_EXPORT_REQUESTED: &str = "data_export.requested";
+    pub const ACCOUNT_DELETED: &str = "account.deleted";
+    pub const ROLE_GRANTED: &str = "role.granted";
+    pub const ROLE_REVOKED: &str = "role.revoked";
+    pub const ADMIN_USER_LOOKED_UP: &str = "admin.user_looked_up";
+    pub const ADMIN_CREDENTIALS_LISTED: &str = "admin.credentials_listed";
+    pub const ADMIN_PASSKEY_DELETED: &str = "admin.passkey_deleted";
+    pub const ADMIN_PERSONAL_ACCESS_TOKEN_REVOKED: &str = "admin.personal_access_token_revoked";
+    pub const ADMIN_SESSION_REVOKED: &str = "admin.session_revoked";
+    pub const ADMIN_FORCED_LOGOUT: &str = "admin.forced_logout";
+    pub const ADMIN_STATUS_CHANGED: &str = "admin.status_changed";
+    pub const ADMIN_AUDIT_LOG_VIEWED: &str = "admin.audit_log_viewed";
+    pub const ADMIN_USERS_LISTED: &str = "admin.users_listed";
+    pub const ADMIN_USERS_EXPORTED: &str = "admin.users_exported";
+}
+
+// Records an action the user performed on their own account.
+pub async fn record<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+    action: &str,
+    metadata: Value,
+) -> Result<(), DbErr> {
+    record_as(db, user_id, user_id, action, metadata).await
+}
+
+// Records an action an operator performed on someone else's account.
+pub async fn record_as<T: ConnectionTrait>(
+    db: &T,
+    actor_id: Uuid,
+    user_id: Uuid,
+    action: &str,
+    metadata: Value,
+) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+
+    repo::create_audit_event(
+        db,
+        repo::audit_event::Model {
+            id: Uuid::now_v7(),
+            user_id: Some(user_id),
+            actor_id: Some(actor_id),
+            actor_client_id: None,
+            action: action.into(),
+            metadata,
+            created_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Actor {
+    User(Uuid),
+    Service(String),
+}
+
+// Records an action no single account is the subject of. An operator's goes into their own
+// log, a service client has none and is recorded by its client id alone.
+pub async fn record_by<T: ConnectionTrait>(
+    db: &T,
+    actor: &Actor,
+    action: &str,
+    metadata: Value,
+) -> Result<(), DbErr> {
+    let (user_id, actor_client_id) = match actor {
+        Actor::User(user_id) => (Some(*user_id), None),
+        Actor::Service(client_id) => (None, Some(client_id.clone())),
+    };
+    let now = Utc::now().naive_utc();
+
+    repo::create_audit_event(
+        db,
+        repo::audit_event::Model {
+            id: Uuid::now_v7(),
+            user_id,
+            actor_id: user_id,
+            actor_client_id,
+            action: action.into(),
+            metadata,
+            created_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+
+    Ok(())
+}
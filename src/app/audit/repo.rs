@@ -0,0 +1,45 @@
+use sea_orm::{
+    ActiveModelTrait as _, ColumnTrait as _, ConnectionTrait, DbErr, EntityTrait as _,
+    IntoActiveModel as _, Order, QueryFilter as _, QueryOrder as _, QuerySelect as _,
+};
+use uuid::Uuid;
+
+pub mod audit_event;
+
+pub async fn create_audit_event<T: ConnectionTrait>(
+    db: &T,
+    model: audit_event::Model,
+) -> Result<audit_event::Model, DbErr> {
+    model.into_active_model().insert(db).await
+}
+
+pub async fn find_audit_events_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Vec<audit_event::Model>, DbErr> {
+    audit_event::Entity::find()
+        .filter(audit_event::Column::UserId.eq(user_id))
+        .order_by(audit_event::Column::Id, Order::Asc)
+        .all(db)
+        .await
+}
+
+// Ids are UUIDv7, so they sort in the order the events were recorded.
+pub async fn find_audit_events_page<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+    after: Option<Uuid>,
+    limit: u64,
+) -> Result<Vec<audit_event::Model>, DbErr> {
+    let mut query = audit_event::Entity::find().filter(audit_event::Column::UserId.eq(user_id));
+
+    if let Some(after) = after {
+        query = query.filter(audit_event::Column::Id.gt(after));
+    }
+
+    query
+        .order_by(audit_event::Column::Id, Order::Asc)
+        .limit(limit)
+        .all(db)
+        .await
+}
@@ -1,14 +1,20 @@
+use std::time::Duration;
+
 use flux_users_api::auth_service_server::AuthServiceServer;
 use grpc::GrpcAuthService;
-use serde::Serialize;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::{task::JoinHandle, time};
 use uuid::Uuid;
 
 use super::state::AppState;
 
+pub(super) mod dpop;
 pub(super) mod error;
 mod grpc;
 mod passkey;
-mod repo;
+pub(super) mod principal;
+pub(super) mod repo;
 mod service;
 pub(super) mod settings;
 
@@ -16,8 +22,58 @@ pub fn auth_service(state: AppState) -> AuthServiceServer<GrpcAuthService> {
     AuthServiceServer::new(GrpcAuthService::new(state))
 }
 
-#[derive(Serialize)]
+pub fn purge_job(state: AppState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(
+            state.settings.auth.deletion.purge_interval,
+        ));
+
+        loop {
+            interval.tick().await;
+
+            match service::purge_deleted_users(&state.db, &state.storage, &state.settings.auth)
+                .await
+            {
+                Ok(0) => {}
+                Ok(purged) => info!("auth: purged {} deleted users", purged),
+                Err(err) => error!("auth: purge failed: {}", err),
+            }
+        }
+    })
+}
+
+// Both kinds of token are signed with the same key, the audience keeps one from being
+// accepted as the other.
+pub mod audience {
+    pub const USER: &str = "flux-users";
+    pub const SERVICE: &str = "flux-users:service";
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Claims {
+    pub aud: String,
     pub sub: Uuid,
     pub exp: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    // For downstream services only, permissions here are always read from the database.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ServiceClaims {
+    pub aud: String,
+    pub sub: String,
+    pub scope: String,
+    pub exp: usize,
 }
@@ -0,0 +1,379 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::extract::OriginalUri;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use hmac::{Hmac, Mac as _};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+use tonic::Request;
+
+use crate::app::state::AppState;
+
+use super::{error::AuthError, settings::DpopSettings};
+
+const HTM: &str = "POST";
+
+#[derive(Deserialize)]
+struct ProofClaims {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: i64,
+    nonce: Option<String>,
+    ath: Option<String>,
+}
+
+// In-process only: with several replicas a captured proof can be replayed once
+// against each other instance within max_age, for the same method and access
+// token. Keep max_age short, or route a client to one instance, where that matters.
+// Nonces don't help, they are valid on every instance.
+#[derive(Clone, Default)]
+pub struct ReplayCache(Arc<Mutex<HashMap<String, i64>>>);
+
+impl ReplayCache {
+    fn insert(&self, key: String, expires_at: i64, now: i64) -> bool {
+        let mut entries = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        entries.retain(|_, expires_at| *expires_at > now);
+
+        entries.insert(key, expires_at).is_none()
+    }
+}
+
+pub fn proof<T>(request: &Request<T>) -> Option<&str> {
+    request.metadata().get("dpop")?.to_str().ok()
+}
+
+// Verifies the DPoP proof of a request, if any, and returns the thumbprint of
+// its key for binding a token that is about to be issued.
+pub fn bind<T>(state: &AppState, request: &Request<T>) -> Result<Option<String>, AuthError> {
+    match proof(request) {
+        Some(proof) => Ok(Some(verify(state, request, proof, None)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn verify<T>(
+    AppState {
+        settings,
+        secret_key,
+        dpop_replay_cache,
+        ..
+    }: &AppState,
+    request: &Request<T>,
+    proof: &str,
+    access_token: Option<&str>,
+) -> Result<String, AuthError> {
+    let settings = &settings.auth.dpop;
+    // The path as routed by axum, i.e. /<package>.<Service>/<Method> for gRPC calls.
+    let OriginalUri(uri) = request
+        .extensions()
+        .get::<OriginalUri>()
+        .ok_or(AuthError::InvalidDpopProof)?;
+    let htu = format!("{}{}", settings.origin, uri.path());
+
+    let header = decode_header(proof)?;
+    if header.typ.as_deref() != Some("dpop+jwt") || !is_asymmetric(header.alg) {
+        return Err(AuthError::InvalidDpopProof);
+    }
+    let jwk = header.jwk.ok_or(AuthError::InvalidDpopProof)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.set_required_spec_claims::<&str>(&[]);
+
+    let claims = decode::<ProofClaims>(proof, &DecodingKey::from_jwk(&jwk)?, &validation)?.claims;
+    let now = Utc::now().timestamp();
+
+    if claims.htm != HTM
+        || strip_query(&claims.htu) != htu
+        || (now - claims.iat).abs() > settings.max_age
+        || claims.ath != access_token.map(|it| sha256(it.as_bytes()))
+    {
+        return Err(AuthError::InvalidDpopProof);
+    }
+
+    if !validate_nonce(secret_key, settings, claims.nonce.as_deref(), now) {
+        return Err(AuthError::UseDpopNonce(nonce(
+            secret_key,
+            window(settings, now),
+        )));
+    }
+
+    let jkt = thumbprint(&jwk)?;
+    if !dpop_replay_cache.insert(
+        format!("{}:{}", jkt, claims.jti),
+        now + settings.max_age,
+        now,
+    ) {
+        return Err(AuthError::InvalidDpopProof);
+    }
+
+    Ok(jkt)
+}
+
+// RFC 7638 thumbprint over the required members in lexicographic order.
+pub fn thumbprint(jwk: &Jwk) -> Result<String, AuthError> {
+    let members = match &jwk.algorithm {
+        AlgorithmParameters::EllipticCurve(params) => format!(
+            r#"{{"crv":"{}","kty":"EC","x":"{}","y":"{}"}}"#,
+            curve(&params.curve),
+            params.x,
+            params.y
+        ),
+        AlgorithmParameters::RSA(params) => {
+            format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, params.e, params.n)
+        }
+        AlgorithmParameters::OctetKeyPair(params) => format!(
+            r#"{{"crv":"{}","kty":"OKP","x":"{}"}}"#,
+            curve(&params.curve),
+            params.x
+        ),
+        AlgorithmParameters::OctetKey(_) => return Err(AuthError::InvalidDpopProof),
+    };
+
+    Ok(sha256(members.as_bytes()))
+}
+
+// A zero nonce_ttl in the config would otherwise divide by zero on every request.
+fn window(settings: &DpopSettings, now: i64) -> i64 {
+    now / settings.nonce_ttl.max(1)
+}
+
+fn validate_nonce(key: &[u8], settings: &DpopSettings, nonce: Option<&str>, now: i64) -> bool {
+    let window = window(settings, now);
+
+    nonce.is_some_and(|it| it == self::nonce(key, window) || it == self::nonce(key, window - 1))
+}
+
+// Keyed with the secret key under its own label, so a nonce is never the hash of a stored secret.
+fn nonce(key: &[u8], window: i64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(b"dpop-nonce:");
+    mac.update(&window.to_be_bytes());
+
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+fn sha256(input: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(input))
+}
+
+fn strip_query(htu: &str) -> &str {
+    htu.split(['?', '#']).next().unwrap_or(htu)
+}
+
+fn is_asymmetric(alg: Algorithm) -> bool {
+    !matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+fn curve(curve: &EllipticCurve) -> &'static str {
+    match curve {
+        EllipticCurve::P256 => "P-256",
+        EllipticCurve::P384 => "P-384",
+        EllipticCurve::P521 => "P-521",
+        EllipticCurve::Ed25519 => "Ed25519",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::OriginalUri;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    use chrono::Utc;
+    use jsonwebtoken::{
+        encode,
+        jwk::{AlgorithmParameters, CommonParameters, Jwk, RSAKeyParameters},
+        Algorithm, EncodingKey, Header,
+    };
+    use p256::{elliptic_curve::sec1::ToEncodedPoint as _, pkcs8::DecodePrivateKey as _};
+    use serde_json::json;
+    use tonic::Request;
+    use uuid::Uuid;
+
+    use crate::app::{
+        auth::{error::AuthError, settings::DpopSettings},
+        state::AppState,
+    };
+
+    use super::{nonce, strip_query, thumbprint, validate_nonce, verify, ReplayCache};
+
+    const KEY: &str = include_str!("testdata/dpop_key.pem");
+    const ORIGIN: &str = "https://theflux.app";
+    const PATH: &str = "/flux.auth.AuthService/Login";
+
+    fn jwk() -> Jwk {
+        let point = p256::SecretKey::from_pkcs8_pem(KEY)
+            .unwrap()
+            .public_key()
+            .to_encoded_point(false);
+
+        serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+            "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+        }))
+        .unwrap()
+    }
+
+    fn proof(htu: &str, nonce: Option<&str>) -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.typ = Some("dpop+jwt".into());
+        header.jwk = Some(jwk());
+
+        let claims = json!({
+            "jti": Uuid::now_v7(),
+            "htm": "POST",
+            "htu": htu,
+            "iat": Utc::now().timestamp(),
+            "nonce": nonce,
+        });
+
+        encode(
+            &header,
+            &claims,
+            &EncodingKey::from_ec_pem(KEY.as_bytes()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn request(path: &str) -> Request<()> {
+        let mut request = Request::new(());
+        request
+            .extensions_mut()
+            .insert(OriginalUri(path.parse().unwrap()));
+        request
+    }
+
+    #[test]
+    fn should_verify_signed_proof() {
+        let mut state = AppState::default();
+        state.secret_key = b"secret key".to_vec();
+        state.settings.auth.dpop = DpopSettings {
+            origin: ORIGIN.into(),
+            max_age: 300,
+            nonce_ttl: 300,
+        };
+        let htu = format!("{}{}", ORIGIN, PATH);
+
+        let nonce = match verify(&state, &request(PATH), &proof(&htu, None), None) {
+            Err(AuthError::UseDpopNonce(nonce)) => nonce,
+            _ => panic!("expected a nonce challenge"),
+        };
+
+        let signed = proof(&htu, Some(&nonce));
+        assert_eq!(
+            verify(&state, &request(PATH), &signed, None).unwrap(),
+            thumbprint(&jwk()).unwrap()
+        );
+        // Replayed
+        assert!(verify(&state, &request(PATH), &signed, None).is_err());
+
+        let signed = proof(&htu, Some(&nonce));
+        assert!(verify(
+            &state,
+            &request("/flux.auth.AuthService/Complete"),
+            &signed,
+            None
+        )
+        .is_err());
+        assert!(verify(&state, &Request::new(()), &signed, None).is_err());
+    }
+
+    #[test]
+    fn should_compute_rfc7638_thumbprint() {
+        let jwk = Jwk {
+            common: CommonParameters::default(),
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: Default::default(),
+                n: "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw".into(),
+                e: "AQAB".into(),
+            }),
+        };
+
+        assert_eq!(
+            thumbprint(&jwk).unwrap(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
+    #[test]
+    fn should_validate_nonce() {
+        let settings = DpopSettings {
+            origin: String::default(),
+            max_age: 300,
+            nonce_ttl: 300,
+        };
+        let now = 1_000_000;
+
+        assert!(validate_nonce(
+            b"KEY",
+            &settings,
+            Some(&nonce(b"KEY", now / 300)),
+            now
+        ));
+        assert!(validate_nonce(
+            b"KEY",
+            &settings,
+            Some(&nonce(b"KEY", now / 300)),
+            now + 300
+        ));
+        assert!(!validate_nonce(
+            b"KEY",
+            &settings,
+            Some(&nonce(b"KEY", now / 300)),
+            now + 600
+        ));
+        assert!(!validate_nonce(
+            b"KEY",
+            &settings,
+            Some(&nonce(b"OTHER", now / 300)),
+            now
+        ));
+        assert!(!validate_nonce(b"KEY", &settings, None, now));
+    }
+
+    #[test]
+    fn should_clamp_zero_nonce_ttl() {
+        let settings = DpopSettings {
+            origin: String::default(),
+            max_age: 300,
+            nonce_ttl: 0,
+        };
+        let now = 1_000_000;
+
+        assert!(validate_nonce(
+            b"KEY",
+            &settings,
+            Some(&nonce(b"KEY", now)),
+            now
+        ));
+    }
+
+    #[test]
+    fn should_reject_replayed_proof() {
+        let cache = ReplayCache::default();
+
+        assert!(cache.insert("JTI".into(), 10, 0));
+        assert!(!cache.insert("JTI".into(), 10, 5));
+        assert!(cache.insert("JTI".into(), 20, 10));
+    }
+
+    #[test]
+    fn should_strip_query() {
+        assert_eq!(
+            strip_query("https://theflux.app/a?b=c#d"),
+            "https://theflux.app/a"
+        );
+    }
+}
@@ -1,4 +1,6 @@
+use chrono::NaiveDateTime;
 use thiserror::Error;
+use tonic::Status;
 
 #[derive(Error, Debug)]
 pub enum AuthError {
@@ -16,4 +18,86 @@ pub enum AuthError {
     UserCredentialNotFound,
     #[error("USER_NOT_FOUND")]
     UserNotFound,
+    #[error("UNAUTHENTICATED")]
+    Unauthenticated,
+    #[error("INVALID_TOKEN")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("INVALID_CLIENT")]
+    InvalidClient,
+    #[error("INVALID_SCOPE")]
+    InvalidScope,
+    #[error("INSUFFICIENT_SCOPE")]
+    InsufficientScope,
+    #[error("INVALID_DPOP_PROOF")]
+    InvalidDpopProof,
+    #[error("USE_DPOP_NONCE")]
+    UseDpopNonce(String),
+    #[error("USER_NOT_VERIFIED")]
+    UserNotVerified,
+    #[error("REAUTHENTICATION_REQUIRED")]
+    ReauthenticationRequired,
+    #[error("INVALID_DEVICE_CODE")]
+    InvalidDeviceCode,
+    #[error("AUTHORIZATION_PENDING")]
+    AuthorizationPending,
+    #[error("SLOW_DOWN")]
+    SlowDown,
+    #[error("ACCESS_DENIED")]
+    AccessDenied,
+    #[error("EXPIRED_TOKEN")]
+    ExpiredToken,
+    #[error("EMAIL_UNAVAILABLE")]
+    EmailUnavailable,
+    #[error("INVALID_EMAIL_CHANGE_TOKEN")]
+    InvalidEmailChangeToken,
+    #[error("USER_SUSPENDED")]
+    UserSuspended(Option<NaiveDateTime>),
+    #[error("USER_BANNED")]
+    UserBanned,
+    #[error("PERMISSION_DENIED")]
+    PermissionDenied,
+}
+
+impl From<AuthError> for Status {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::Unauthenticated | AuthError::InvalidToken(_) | AuthError::InvalidClient => {
+                Self::unauthenticated(error.to_string())
+            }
+            AuthError::InvalidScope => Self::invalid_argument(error.to_string()),
+            AuthError::InvalidDeviceCode | AuthError::InvalidEmailChangeToken => {
+                Self::not_found(error.to_string())
+            }
+            AuthError::EmailUnavailable => Self::already_exists(error.to_string()),
+            AuthError::AuthorizationPending | AuthError::ExpiredToken => {
+                Self::failed_precondition(error.to_string())
+            }
+            AuthError::SlowDown => Self::resource_exhausted(error.to_string()),
+            AuthError::AccessDenied => Self::permission_denied(error.to_string()),
+            AuthError::InsufficientScope => Self::permission_denied(error.to_string()),
+            AuthError::InvalidDpopProof
+            | AuthError::UserNotVerified
+            | AuthError::ReauthenticationRequired => Self::unauthenticated(error.to_string()),
+            AuthError::UserSuspended(until) => {
+                let mut status = Self::permission_denied(error.to_string());
+                if let Some(Ok(until)) = until.map(|it| it.and_utc().to_rfc3339().parse()) {
+                    status.metadata_mut().insert("suspended-until", until);
+                }
+
+                status
+            }
+            AuthError::UserBanned | AuthError::PermissionDenied => {
+                Self::permission_denied(error.to_string())
+            }
+            AuthError::UseDpopNonce(ref nonce) => {
+                let mut status = Self::unauthenticated(error.to_string());
+                if let Ok(nonce) = nonce.parse() {
+                    status.metadata_mut().insert("dpop-nonce", nonce);
+                }
+
+                status
+            }
+            _ => Self::internal(error.to_string()),
+        }
+    }
 }
@@ -1,11 +1,26 @@
 use flux_users_api::{
-    auth_service_server::AuthService, CompleteRequest, CompleteResponse, JoinRequest, JoinResponse,
-    LoginRequest, LoginResponse, MeRequest, MeResponse,
+    auth_service_server::AuthService, ApproveDeviceAuthorizationRequest,
+    ApproveDeviceAuthorizationResponse, ChangeEmailRequest, ChangeEmailResponse, CompleteRequest,
+    CompleteResponse, ConfirmEmailChangeRequest, ConfirmEmailChangeResponse,
+    CreatePersonalAccessTokenRequest, CreatePersonalAccessTokenResponse, DeleteAccountRequest,
+    DeleteAccountResponse, ExportMyDataRequest, ExportMyDataResponse, GetDataExportRequest,
+    GetDataExportResponse, GrantRoleRequest, GrantRoleResponse, IssueServiceTokenRequest,
+    IssueServiceTokenResponse, JoinRequest, JoinResponse, ListPersonalAccessTokensRequest,
+    ListPersonalAccessTokensResponse, LoginRequest, LoginResponse, MeRequest, MeResponse,
+    PollDeviceAuthorizationRequest, PollDeviceAuthorizationResponse, ReauthenticateRequest,
+    ReauthenticateResponse, RevokePersonalAccessTokenRequest, RevokePersonalAccessTokenResponse,
+    RevokeRoleRequest, RevokeRoleResponse, StartDeviceAuthorizationRequest,
+    StartDeviceAuthorizationResponse, StartReauthenticationRequest, StartReauthenticationResponse,
 };
 use tonic::{Request, Response, Status};
+use uuid::Uuid;
 
-use super::service;
-use crate::app::{error::AppError, state::AppState};
+use super::{
+    dpop,
+    principal::{self, permission},
+    service,
+};
+use crate::app::{error::AppError, locale, state::AppState};
 
 pub struct GrpcAuthService {
     pub state: AppState,
@@ -26,7 +41,8 @@ impl AuthService for GrpcAuthService {
     }
 
     async fn login(&self, req: Request<LoginRequest>) -> Result<Response<LoginResponse>, Status> {
-        let response = login(&self.state, req.into_inner()).await?;
+        let jkt = dpop::bind(&self.state, &req)?;
+        let response = login(&self.state, req.into_inner(), jkt).await?;
 
         Ok(Response::new(response))
     }
@@ -35,7 +51,13 @@ impl AuthService for GrpcAuthService {
         &self,
         request: Request<CompleteRequest>,
     ) -> Result<Response<CompleteResponse>, Status> {
-        let response = complete(&self.state, request.into_inner()).await?;
+        let jkt = dpop::bind(&self.state, &request)?;
+        let locale = locale::from_request(
+            &self.state.settings.locale,
+            request.get_ref().locale.as_deref(),
+            request.metadata(),
+        )?;
+        let response = complete(&self.state, request.into_inner(), jkt, locale).await?;
 
         Ok(Response::new(response))
     }
@@ -45,6 +67,205 @@ impl AuthService for GrpcAuthService {
 
         Ok(Response::new(response))
     }
+
+    async fn issue_service_token(
+        &self,
+        request: Request<IssueServiceTokenRequest>,
+    ) -> Result<Response<IssueServiceTokenResponse>, Status> {
+        let response = issue_service_token(&self.state, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn create_personal_access_token(
+        &self,
+        request: Request<CreatePersonalAccessTokenRequest>,
+    ) -> Result<Response<CreatePersonalAccessTokenResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_recent_auth(self.state.settings.auth.reauthentication.max_age)?;
+
+        let response =
+            create_personal_access_token(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_personal_access_tokens(
+        &self,
+        request: Request<ListPersonalAccessTokensRequest>,
+    ) -> Result<Response<ListPersonalAccessTokensResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = list_personal_access_tokens(&self.state, user_id).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn revoke_personal_access_token(
+        &self,
+        request: Request<RevokePersonalAccessTokenRequest>,
+    ) -> Result<Response<RevokePersonalAccessTokenResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response =
+            revoke_personal_access_token(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn start_reauthentication(
+        &self,
+        request: Request<StartReauthenticationRequest>,
+    ) -> Result<Response<StartReauthenticationResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = start_reauthentication(&self.state, user_id).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn reauthenticate(
+        &self,
+        request: Request<ReauthenticateRequest>,
+    ) -> Result<Response<ReauthenticateResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+        let jkt = dpop::bind(&self.state, &request)?;
+
+        let response = reauthenticate(&self.state, user_id, request.into_inner(), jkt).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn start_device_authorization(
+        &self,
+        request: Request<StartDeviceAuthorizationRequest>,
+    ) -> Result<Response<StartDeviceAuthorizationResponse>, Status> {
+        let response = start_device_authorization(&self.state, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn poll_device_authorization(
+        &self,
+        request: Request<PollDeviceAuthorizationRequest>,
+    ) -> Result<Response<PollDeviceAuthorizationResponse>, Status> {
+        let jkt = dpop::bind(&self.state, &request)?;
+        let response = poll_device_authorization(&self.state, request.into_inner(), jkt).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn approve_device_authorization(
+        &self,
+        request: Request<ApproveDeviceAuthorizationRequest>,
+    ) -> Result<Response<ApproveDeviceAuthorizationResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_recent_auth(self.state.settings.auth.reauthentication.max_age)?;
+
+        let response =
+            approve_device_authorization(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn change_email(
+        &self,
+        request: Request<ChangeEmailRequest>,
+    ) -> Result<Response<ChangeEmailResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_recent_auth(self.state.settings.auth.reauthentication.max_age)?;
+
+        let response = change_email(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn confirm_email_change(
+        &self,
+        request: Request<ConfirmEmailChangeRequest>,
+    ) -> Result<Response<ConfirmEmailChangeResponse>, Status> {
+        let response = confirm_email_change(&self.state, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn delete_account(
+        &self,
+        request: Request<DeleteAccountRequest>,
+    ) -> Result<Response<DeleteAccountResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_recent_auth(self.state.settings.auth.reauthentication.max_age)?;
+
+        let response = delete_account(&self.state, user_id).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn export_my_data(
+        &self,
+        request: Request<ExportMyDataRequest>,
+    ) -> Result<Response<ExportMyDataResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_recent_auth(self.state.settings.auth.reauthentication.max_age)?;
+
+        let response = export_my_data(&self.state, user_id).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn get_data_export(
+        &self,
+        request: Request<GetDataExportRequest>,
+    ) -> Result<Response<GetDataExportResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = get_data_export(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn grant_role(
+        &self,
+        request: Request<GrantRoleRequest>,
+    ) -> Result<Response<GrantRoleResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::ROLES_WRITE)
+            .await?;
+
+        let response = grant_role(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn revoke_role(
+        &self,
+        request: Request<RevokeRoleRequest>,
+    ) -> Result<Response<RevokeRoleResponse>, Status> {
+        let actor_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_permission(&self.state.db, permission::ROLES_WRITE)
+            .await?;
+
+        let response = revoke_role(&self.state, actor_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
 }
 
 async fn join(
@@ -139,8 +360,13 @@ async fn login(
         ..
     }: &AppState,
     req: LoginRequest,
+    jkt: Option<String>,
 ) -> Result<LoginResponse, AppError> {
-    let res = service::login(db, &settings.auth, private_key, req.try_into()?).await?;
+    let req = service::login::Request {
+        jkt,
+        ..req.try_into()?
+    };
+    let res = service::login(db, &settings.auth, private_key, req).await?;
 
     Ok(res.into())
 }
@@ -177,10 +403,18 @@ async fn complete(
         settings,
         db,
         private_key,
+        ..
     }: &AppState,
     request: CompleteRequest,
+    jkt: Option<String>,
+    locale: String,
 ) -> Result<CompleteResponse, AppError> {
-    let response = service::complete(db, &settings.auth, private_key, request.try_into()?).await?;
+    let request = service::complete::Request {
+        jkt,
+        locale,
+        ..request.try_into()?
+    };
+    let response = service::complete(db, &settings.auth, private_key, request).await?;
 
     Ok(response.into())
 }
@@ -200,9 +434,11 @@ mod complete {
         fn try_from(request: CompleteRequest) -> Result<Self, Self::Error> {
             let data = Self {
                 first_name: request.first_name().into(),
-                last_name: request.first_name().into(),
+                last_name: request.last_name().into(),
                 locale: request.locale().into(),
                 credential: serde_json::from_str(request.credential())?,
+                timezone: request.timezone,
+                jkt: None,
             };
 
             data.validate()?;
@@ -216,6 +452,42 @@ mod complete {
             CompleteResponse { jwt: Some(res.jwt) }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+        use flux_users_api::CompleteRequest;
+        use serde_json::json;
+
+        use crate::app::auth::service::complete::Request;
+
+        #[test]
+        fn should_map_names() {
+            let client_data =
+                json!({ "type": "webauthn.create", "challenge": "", "origin": "RP" }).to_string();
+            let credential = json!({
+                "id": "ID",
+                "response": {
+                    "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                    "publicKey": "",
+                    "publicKeyAlgorithm": -7,
+                },
+            });
+
+            let request: Request = CompleteRequest {
+                first_name: Some("First".into()),
+                last_name: Some("Last".into()),
+                locale: Some("en".into()),
+                credential: Some(credential.to_string()),
+                timezone: None,
+            }
+            .try_into()
+            .unwrap();
+
+            assert_eq!(request.first_name, "First");
+            assert_eq!(request.last_name, "Last");
+        }
+    }
 }
 
 async fn me(AppState { db, .. }: &AppState, request: MeRequest) -> Result<MeResponse, AppError> {
@@ -258,9 +530,827 @@ mod me {
                     last_name: Some(user.last_name.clone()),
                     name: Some(user.name()),
                     abbr: Some(user.abbr()),
-                    color: Some(user.color()),
+                    color: Some(user.avatar_color().light.hsla()),
+                    avatar_url: user.picture_url.clone(),
                 }),
             })
         }
     }
 }
+
+async fn issue_service_token(
+    AppState {
+        settings,
+        db,
+        private_key,
+        secret_key,
+        ..
+    }: &AppState,
+    request: IssueServiceTokenRequest,
+) -> Result<IssueServiceTokenResponse, AppError> {
+    let response = service::issue_service_token(
+        db,
+        &settings.auth,
+        private_key,
+        secret_key,
+        request.try_into()?,
+    )
+    .await?;
+
+    Ok(response.into())
+}
+
+mod issue_service_token {
+    use flux_users_api::{IssueServiceTokenRequest, IssueServiceTokenResponse};
+    use validator::{Validate as _, ValidationError, ValidationErrors};
+
+    use crate::app::{
+        auth::service::issue_service_token::{Credentials, Request, Response},
+        error::AppError,
+    };
+
+    impl TryFrom<IssueServiceTokenRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: IssueServiceTokenRequest) -> Result<Self, Self::Error> {
+            let credentials = match (request.client_secret, request.client_assertion) {
+                (Some(secret), None) => Credentials::Secret(secret),
+                (None, Some(assertion)) => Credentials::Assertion(assertion),
+                _ => {
+                    let mut errors = ValidationErrors::new();
+                    errors.add("client_secret", ValidationError::new("exactly_one"));
+                    return Err(errors.into());
+                }
+            };
+
+            let data = Self {
+                client_id: request.client_id.unwrap_or_default(),
+                credentials,
+                scopes: request.scopes,
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    impl From<Response> for IssueServiceTokenResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                token: Some(res.token),
+                expires_in: Some(res.expires_in),
+                scopes: res.scopes,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::IssueServiceTokenRequest;
+
+        use crate::app::{auth::service::issue_service_token::Request, error::AppError};
+
+        #[test]
+        fn should_require_exactly_one_credential() {
+            let request = |secret: Option<&str>, assertion: Option<&str>| {
+                let result: Result<Request, AppError> = IssueServiceTokenRequest {
+                    client_id: Some("analytics".into()),
+                    client_secret: secret.map(Into::into),
+                    client_assertion: assertion.map(Into::into),
+                    scopes: vec![],
+                }
+                .try_into();
+                matches!(
+                    result,
+                    Err(AppError::Validation(it)) if it.field_errors().contains_key("client_secret")
+                )
+            };
+
+            assert!(request(None, None));
+            assert!(request(Some("secret"), Some("assertion")));
+        }
+    }
+}
+
+async fn create_personal_access_token(
+    AppState { db, secret_key, .. }: &AppState,
+    user_id: Uuid,
+    request: CreatePersonalAccessTokenRequest,
+) -> Result<CreatePersonalAccessTokenResponse, AppError> {
+    let response =
+        service::create_personal_access_token(db, secret_key, user_id, request.try_into()?).await?;
+
+    Ok(response.into())
+}
+
+mod create_personal_access_token {
+    use chrono::DateTime;
+    use flux_users_api::{CreatePersonalAccessTokenRequest, CreatePersonalAccessTokenResponse};
+    use validator::{Validate as _, ValidationError, ValidationErrors};
+
+    use crate::app::{
+        auth::service::create_personal_access_token::{Request, Response},
+        error::AppError,
+    };
+
+    impl TryFrom<CreatePersonalAccessTokenRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: CreatePersonalAccessTokenRequest) -> Result<Self, Self::Error> {
+            let expires_at = request
+                .expires_at
+                .map(|expires_at| {
+                    DateTime::from_timestamp(expires_at, 0)
+                        .map(|it| it.naive_utc())
+                        .ok_or_else(|| {
+                            let mut errors = ValidationErrors::new();
+                            errors.add("expires_at", ValidationError::new("timestamp"));
+                            errors
+                        })
+                })
+                .transpose()?;
+
+            let data = Self {
+                name: request.name().trim().into(),
+                scopes: request.scopes,
+                expires_at,
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    impl From<Response> for CreatePersonalAccessTokenResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                token: Some(res.token),
+                personal_access_token: Some(res.personal_access_token.into()),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::CreatePersonalAccessTokenRequest;
+
+        use crate::app::{auth::service::create_personal_access_token::Request, error::AppError};
+
+        #[test]
+        fn should_reject_out_of_range_expires_at() {
+            let result: Result<Request, AppError> = CreatePersonalAccessTokenRequest {
+                name: Some("ci".into()),
+                scopes: vec![],
+                expires_at: Some(i64::MAX),
+            }
+            .try_into();
+
+            assert!(matches!(
+                result,
+                Err(AppError::Validation(it)) if it.field_errors().contains_key("expires_at")
+            ));
+        }
+    }
+}
+
+async fn list_personal_access_tokens(
+    AppState { db, .. }: &AppState,
+    user_id: Uuid,
+) -> Result<ListPersonalAccessTokensResponse, AppError> {
+    let response = service::list_personal_access_tokens(db, user_id).await?;
+
+    Ok(response.into())
+}
+
+mod list_personal_access_tokens {
+    use flux_users_api::{ListPersonalAccessTokensResponse, PersonalAccessToken};
+
+    use crate::app::auth::{repo, service::list_personal_access_tokens::Response};
+
+    impl From<Response> for ListPersonalAccessTokensResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                personal_access_tokens: res
+                    .personal_access_tokens
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            }
+        }
+    }
+
+    impl From<repo::personal_access_token::Model> for PersonalAccessToken {
+        fn from(model: repo::personal_access_token::Model) -> Self {
+            Self {
+                personal_access_token_id: Some(model.id.into()),
+                scopes: model.scopes(),
+                name: Some(model.name),
+                prefix: Some(model.prefix),
+                created_at: Some(model.created_at.and_utc().timestamp()),
+                expires_at: model.expires_at.map(|it| it.and_utc().timestamp()),
+                last_used_at: model.last_used_at.map(|it| it.and_utc().timestamp()),
+            }
+        }
+    }
+}
+
+async fn revoke_personal_access_token(
+    AppState { db, .. }: &AppState,
+    user_id: Uuid,
+    request: RevokePersonalAccessTokenRequest,
+) -> Result<RevokePersonalAccessTokenResponse, AppError> {
+    service::revoke_personal_access_token(db, user_id, request.try_into()?).await?;
+
+    Ok(RevokePersonalAccessTokenResponse {})
+}
+
+mod revoke_personal_access_token {
+    use flux_users_api::RevokePersonalAccessTokenRequest;
+    use uuid::Uuid;
+    use validator::{ValidationError, ValidationErrors};
+
+    use crate::app::{auth::service::revoke_personal_access_token::Request, error::AppError};
+
+    impl TryFrom<RevokePersonalAccessTokenRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: RevokePersonalAccessTokenRequest) -> Result<Self, Self::Error> {
+            Ok(Self {
+                id: Uuid::parse_str(request.personal_access_token_id()).map_err(|_| {
+                    let mut errors = ValidationErrors::new();
+                    errors.add("personal_access_token_id", ValidationError::new("uuid"));
+                    errors
+                })?,
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::RevokePersonalAccessTokenRequest;
+
+        use crate::app::{auth::service::revoke_personal_access_token::Request, error::AppError};
+
+        #[test]
+        fn should_reject_invalid_id() {
+            let result: Result<Request, AppError> = RevokePersonalAccessTokenRequest {
+                personal_access_token_id: Some("nope".into()),
+            }
+            .try_into();
+
+            assert!(matches!(
+                result,
+                Err(AppError::Validation(it))
+                    if it.field_errors().contains_key("personal_access_token_id")
+            ));
+        }
+    }
+}
+
+async fn start_reauthentication(
+    AppState { settings, db, .. }: &AppState,
+    user_id: Uuid,
+) -> Result<StartReauthenticationResponse, AppError> {
+    let response = service::start_reauthentication(db, &settings.auth, user_id).await?;
+
+    Ok(response.into())
+}
+
+mod start_reauthentication {
+    use flux_users_api::StartReauthenticationResponse;
+    use serde_json::json;
+
+    use crate::app::auth::service::join::Response;
+
+    impl From<Response> for StartReauthenticationResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                response: Some(json!(res).to_string()),
+            }
+        }
+    }
+}
+
+async fn reauthenticate(
+    AppState {
+        settings,
+        db,
+        private_key,
+        ..
+    }: &AppState,
+    user_id: Uuid,
+    request: ReauthenticateRequest,
+    jkt: Option<String>,
+) -> Result<ReauthenticateResponse, AppError> {
+    let request = service::login::Request {
+        jkt,
+        ..request.try_into()?
+    };
+    let response =
+        service::reauthenticate(db, &settings.auth, private_key, user_id, request).await?;
+
+    Ok(response.into())
+}
+
+mod reauthenticate {
+    use flux_users_api::{ReauthenticateRequest, ReauthenticateResponse};
+    use validator::Validate as _;
+
+    use crate::app::{
+        auth::service::{login, reauthenticate::Response},
+        error::AppError,
+    };
+
+    impl TryFrom<ReauthenticateRequest> for login::Request {
+        type Error = AppError;
+
+        fn try_from(req: ReauthenticateRequest) -> Result<Self, Self::Error> {
+            let data: Self = serde_json::from_str(req.request())?;
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    impl From<Response> for ReauthenticateResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                jwt: Some(res.jwt),
+                expires_in: Some(res.expires_in),
+            }
+        }
+    }
+}
+
+async fn start_device_authorization(
+    AppState {
+        settings,
+        db,
+        secret_key,
+        ..
+    }: &AppState,
+    request: StartDeviceAuthorizationRequest,
+) -> Result<StartDeviceAuthorizationResponse, AppError> {
+    let response =
+        service::start_device_authorization(db, &settings.auth, secret_key, request.try_into()?)
+            .await?;
+
+    Ok(response.into())
+}
+
+mod start_device_authorization {
+    use flux_users_api::{StartDeviceAuthorizationRequest, StartDeviceAuthorizationResponse};
+    use validator::Validate as _;
+
+    use crate::app::{
+        auth::service::start_device_authorization::{Request, Response},
+        error::AppError,
+    };
+
+    impl TryFrom<StartDeviceAuthorizationRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: StartDeviceAuthorizationRequest) -> Result<Self, Self::Error> {
+            let data = Self {
+                client_name: request.client_name.map(|it| it.trim().into()),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    impl From<Response> for StartDeviceAuthorizationResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                device_code: Some(res.device_code),
+                user_code: Some(res.user_code),
+                verification_uri: Some(res.verification_uri),
+                verification_uri_complete: Some(res.verification_uri_complete),
+                expires_in: Some(res.expires_in),
+                interval: Some(res.interval),
+            }
+        }
+    }
+}
+
+async fn poll_device_authorization(
+    AppState {
+        db,
+        private_key,
+        secret_key,
+        ..
+    }: &AppState,
+    request: PollDeviceAuthorizationRequest,
+    jkt: Option<String>,
+) -> Result<PollDeviceAuthorizationResponse, AppError> {
+    let request = service::poll_device_authorization::Request {
+        jkt,
+        ..request.try_into()?
+    };
+    let response = service::poll_device_authorization(db, private_key, secret_key, request).await?;
+
+    Ok(response.into())
+}
+
+mod poll_device_authorization {
+    use flux_users_api::{PollDeviceAuthorizationRequest, PollDeviceAuthorizationResponse};
+    use validator::Validate as _;
+
+    use crate::app::{
+        auth::service::poll_device_authorization::{Request, Response},
+        error::AppError,
+    };
+
+    impl TryFrom<PollDeviceAuthorizationRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: PollDeviceAuthorizationRequest) -> Result<Self, Self::Error> {
+            let data = Self {
+                device_code: request.device_code().into(),
+                jkt: None,
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    impl From<Response> for PollDeviceAuthorizationResponse {
+        fn from(res: Response) -> Self {
+            Self { jwt: Some(res.jwt) }
+        }
+    }
+}
+
+async fn approve_device_authorization(
+    AppState { db, .. }: &AppState,
+    user_id: Uuid,
+    request: ApproveDeviceAuthorizationRequest,
+) -> Result<ApproveDeviceAuthorizationResponse, AppError> {
+    service::approve_device_authorization(db, user_id, request.try_into()?).await?;
+
+    Ok(ApproveDeviceAuthorizationResponse {})
+}
+
+mod approve_device_authorization {
+    use flux_users_api::ApproveDeviceAuthorizationRequest;
+    use validator::Validate as _;
+
+    use crate::app::{
+        auth::service::{
+            approve_device_authorization::Request, start_device_authorization::normalize_user_code,
+        },
+        error::AppError,
+    };
+
+    impl TryFrom<ApproveDeviceAuthorizationRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: ApproveDeviceAuthorizationRequest) -> Result<Self, Self::Error> {
+            let data = Self {
+                user_code: normalize_user_code(request.user_code()),
+                approve: request.approve(),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+}
+
+async fn change_email(
+    AppState {
+        settings,
+        db,
+        mailer,
+        secret_key,
+        ..
+    }: &AppState,
+    user_id: Uuid,
+    request: ChangeEmailRequest,
+) -> Result<ChangeEmailResponse, AppError> {
+    service::change_email(
+        db,
+        &settings.auth,
+        secret_key,
+        mailer,
+        user_id,
+        request.try_into()?,
+    )
+    .await?;
+
+    Ok(ChangeEmailResponse {})
+}
+
+mod change_email {
+    use flux_users_api::ChangeEmailRequest;
+    use validator::Validate as _;
+
+    use crate::app::{auth::service::change_email::Request, error::AppError};
+
+    impl TryFrom<ChangeEmailRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: ChangeEmailRequest) -> Result<Self, Self::Error> {
+            let data = Self {
+                email: request.email().trim().to_lowercase(),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+}
+
+async fn confirm_email_change(
+    AppState { db, secret_key, .. }: &AppState,
+    request: ConfirmEmailChangeRequest,
+) -> Result<ConfirmEmailChangeResponse, AppError> {
+    service::confirm_email_change(db, secret_key, request.try_into()?).await?;
+
+    Ok(ConfirmEmailChangeResponse {})
+}
+
+mod confirm_email_change {
+    use flux_users_api::ConfirmEmailChangeRequest;
+    use validator::Validate as _;
+
+    use crate::app::{auth::service::confirm_email_change::Request, error::AppError};
+
+    impl TryFrom<ConfirmEmailChangeRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: ConfirmEmailChangeRequest) -> Result<Self, Self::Error> {
+            let data = Self {
+                token: request.token().into(),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+}
+
+async fn delete_account(
+    AppState { db, storage, .. }: &AppState,
+    user_id: Uuid,
+) -> Result<DeleteAccountResponse, AppError> {
+    service::delete_account(db, storage, user_id).await?;
+
+    Ok(DeleteAccountResponse {})
+}
+
+async fn export_my_data(
+    AppState { settings, db, .. }: &AppState,
+    user_id: Uuid,
+) -> Result<ExportMyDataResponse, AppError> {
+    let response = service::export_my_data(db, &settings.auth, user_id).await?;
+
+    Ok(response.into())
+}
+
+mod export_my_data {
+    use flux_users_api::{DataExport, ExportMyDataResponse};
+
+    use crate::app::auth::{repo, service::export_my_data::Response};
+
+    impl From<Response> for ExportMyDataResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                data_export: Some(res.data_export.into()),
+            }
+        }
+    }
+
+    impl From<repo::data_export::Model> for DataExport {
+        fn from(model: repo::data_export::Model) -> Self {
+            Self {
+                data_export_id: Some(model.id.into()),
+                status: Some(
+                    match model.status {
+                        repo::data_export::Status::Pending => "pending",
+                        repo::data_export::Status::Ready => "ready",
+                        repo::data_export::Status::Failed => "failed",
+                    }
+                    .into(),
+                ),
+                created_at: Some(model.created_at.and_utc().timestamp()),
+                expires_at: Some(model.expires_at.and_utc().timestamp()),
+                data: model.data.map(|it| it.to_string()),
+            }
+        }
+    }
+}
+
+async fn get_data_export(
+    AppState { settings, db, .. }: &AppState,
+    user_id: Uuid,
+    request: GetDataExportRequest,
+) -> Result<GetDataExportResponse, AppError> {
+    let response =
+        service::get_data_export(db, &settings.auth, user_id, request.try_into()?).await?;
+
+    Ok(response.into())
+}
+
+mod get_data_export {
+    use flux_users_api::{GetDataExportRequest, GetDataExportResponse};
+    use uuid::Uuid;
+    use validator::{ValidationError, ValidationErrors};
+
+    use crate::app::{
+        auth::service::get_data_export::{Request, Response},
+        error::AppError,
+    };
+
+    impl TryFrom<GetDataExportRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: GetDataExportRequest) -> Result<Self, Self::Error> {
+            Ok(Self {
+                id: Uuid::parse_str(request.data_export_id()).map_err(|_| {
+                    let mut errors = ValidationErrors::new();
+                    errors.add("data_export_id", ValidationError::new("uuid"));
+                    errors
+                })?,
+            })
+        }
+    }
+
+    impl From<Response> for GetDataExportResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                data_export: Some(res.data_export.into()),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::GetDataExportRequest;
+
+        use crate::app::{auth::service::get_data_export::Request, error::AppError};
+
+        #[test]
+        fn should_reject_invalid_id() {
+            let result: Result<Request, AppError> = GetDataExportRequest {
+                data_export_id: Some("nope".into()),
+            }
+            .try_into();
+
+            assert!(matches!(
+                result,
+                Err(AppError::Validation(it)) if it.field_errors().contains_key("data_export_id")
+            ));
+        }
+    }
+}
+
+async fn grant_role(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: GrantRoleRequest,
+) -> Result<GrantRoleResponse, AppError> {
+    service::grant_role(db, actor_id, request.try_into()?).await?;
+
+    Ok(GrantRoleResponse {})
+}
+
+async fn revoke_role(
+    AppState { db, .. }: &AppState,
+    actor_id: Uuid,
+    request: RevokeRoleRequest,
+) -> Result<RevokeRoleResponse, AppError> {
+    service::revoke_role(db, actor_id, request.try_into()?).await?;
+
+    Ok(RevokeRoleResponse {})
+}
+
+mod grant_role {
+    use flux_users_api::GrantRoleRequest;
+    use uuid::Uuid;
+    use validator::{Validate as _, ValidationError, ValidationErrors};
+
+    use crate::app::{auth::service::grant_role::Request, error::AppError};
+
+    impl TryFrom<GrantRoleRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: GrantRoleRequest) -> Result<Self, Self::Error> {
+            let user_id = Uuid::parse_str(request.user_id()).map_err(|_| {
+                let mut errors = ValidationErrors::new();
+                errors.add("user_id", ValidationError::new("uuid"));
+                errors
+            })?;
+
+            let data = Self {
+                user_id,
+                role: request.role().trim().to_lowercase(),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::GrantRoleRequest;
+        use uuid::Uuid;
+
+        use crate::app::{auth::service::grant_role::Request, error::AppError};
+
+        #[test]
+        fn should_normalize_role() {
+            let user_id = Uuid::now_v7();
+            let request: Request = GrantRoleRequest {
+                user_id: Some(user_id.to_string()),
+                role: Some(" Admin ".into()),
+            }
+            .try_into()
+            .unwrap();
+
+            assert_eq!(request.user_id, user_id);
+            assert_eq!(request.role, "admin");
+        }
+
+        #[test]
+        fn should_reject_invalid_requests() {
+            let result: Result<Request, AppError> = GrantRoleRequest {
+                user_id: Some("nope".into()),
+                role: Some("admin".into()),
+            }
+            .try_into();
+            assert!(
+                matches!(result, Err(AppError::Validation(it)) if it.field_errors().contains_key("user_id"))
+            );
+
+            let result: Result<Request, AppError> = GrantRoleRequest {
+                user_id: Some(Uuid::now_v7().to_string()),
+                role: Some("  ".into()),
+            }
+            .try_into();
+            assert!(
+                matches!(result, Err(AppError::Validation(it)) if it.field_errors().contains_key("role"))
+            );
+        }
+    }
+}
+
+mod revoke_role {
+    use flux_users_api::RevokeRoleRequest;
+    use uuid::Uuid;
+    use validator::{Validate as _, ValidationError, ValidationErrors};
+
+    use crate::app::{auth::service::revoke_role::Request, error::AppError};
+
+    impl TryFrom<RevokeRoleRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: RevokeRoleRequest) -> Result<Self, Self::Error> {
+            let user_id = Uuid::parse_str(request.user_id()).map_err(|_| {
+                let mut errors = ValidationErrors::new();
+                errors.add("user_id", ValidationError::new("uuid"));
+                errors
+            })?;
+
+            let data = Self {
+                user_id,
+                role: request.role().trim().to_lowercase(),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use flux_users_api::RevokeRoleRequest;
+        use uuid::Uuid;
+
+        use crate::app::{auth::service::revoke_role::Request, error::AppError};
+
+        #[test]
+        fn should_reject_invalid_requests() {
+            let result: Result<Request, AppError> = RevokeRoleRequest {
+                user_id: None,
+                role: Some("admin".into()),
+            }
+            .try_into();
+            assert!(
+                matches!(result, Err(AppError::Validation(it)) if it.field_errors().contains_key("user_id"))
+            );
+
+            let result: Result<Request, AppError> = RevokeRoleRequest {
+                user_id: Some(Uuid::now_v7().to_string()),
+                role: None,
+            }
+            .try_into();
+            assert!(
+                matches!(result, Err(AppError::Validation(it)) if it.field_errors().contains_key("role"))
+            );
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use sea_orm::DbConn;
+use serde::Deserialize;
+use tonic::{metadata::MetadataMap, Request};
+use uuid::Uuid;
+
+use crate::app::{audit::Actor, error::AppError, state::AppState};
+
+use super::{
+    audience, dpop,
+    error::AuthError,
+    repo,
+    service::{ensure_active, verify_secret},
+    Claims, ServiceClaims,
+};
+
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "flux_pat_";
+const PERSONAL_ACCESS_TOKEN_LOOKUP_LEN: usize = 8;
+const LAST_USED_AT_INTERVAL: i64 = 300;
+
+pub mod scope {
+    pub const USERS_READ: &str = "users:read";
+    // Service clients only, operators need the users:list permission instead.
+    pub const USERS_LIST: &str = "users:list";
+
+    pub const USER_SCOPES: &[&str] = &[USERS_READ];
+}
+
+// Granted to users through roles, see the roles and role_permissions tables.
+pub mod permission {
+    pub const USERS_LOOKUP: &str = "users:lookup";
+    pub const USERS_CREDENTIALS_READ: &str = "users:credentials:read";
+    pub const USERS_CREDENTIALS_WRITE: &str = "users:credentials:write";
+    pub const USERS_SESSIONS_REVOKE: &str = "users:sessions:revoke";
+    pub const USERS_STATUS_WRITE: &str = "users:status:write";
+    pub const USERS_EMAIL_SEARCH: &str = "users:email:search";
+    pub const USERS_LIST: &str = "users:list";
+    pub const AUDIT_READ: &str = "audit:read";
+    pub const ROLES_WRITE: &str = "roles:write";
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Principal {
+    User {
+        user_id: Uuid,
+        // None for session tokens, which carry the user's full access
+        scopes: Option<Vec<String>>,
+        auth_time: Option<i64>,
+    },
+    Service {
+        client_id: String,
+        scopes: Vec<String>,
+    },
+}
+
+impl Principal {
+    pub fn require_scope(&self, scope: &str) -> Result<(), AuthError> {
+        let granted = match self {
+            Self::User { scopes: None, .. } => true,
+            Self::User {
+                scopes: Some(scopes),
+                ..
+            }
+            | Self::Service { scopes, .. } => scopes.iter().any(|it| it == scope),
+        };
+
+        if !granted {
+            return Err(AuthError::InsufficientScope);
+        }
+
+        Ok(())
+    }
+
+    pub fn require_session(&self) -> Result<Uuid, AuthError> {
+        match self {
+            Self::User {
+                user_id,
+                scopes: None,
+                ..
+            } => Ok(*user_id),
+            _ => Err(AuthError::InsufficientScope),
+        }
+    }
+
+    pub fn require_recent_auth(&self, max_age: i64) -> Result<Uuid, AuthError> {
+        let user_id = self.require_session()?;
+
+        match self {
+            Self::User {
+                auth_time: Some(auth_time),
+                ..
+            } if Utc::now().timestamp() - auth_time <= max_age => Ok(user_id),
+            _ => Err(AuthError::ReauthenticationRequired),
+        }
+    }
+
+    // Permissions are resolved from the database on every call rather than from the roles
+    // claim, so a revoked role takes effect before the token expires.
+    pub async fn require_permission(
+        &self,
+        db: &DbConn,
+        permission: &str,
+    ) -> Result<Uuid, AppError> {
+        let user_id = self.require_session()?;
+
+        if !self.has_permission(db, permission).await? {
+            return Err(AuthError::PermissionDenied.into());
+        }
+
+        Ok(user_id)
+    }
+
+    // For calls open to operators and service clients alike. A service client is checked
+    // against its scopes, everyone else against their permissions.
+    pub async fn require_scope_or_permission(
+        &self,
+        db: &DbConn,
+        scope: &str,
+        permission: &str,
+    ) -> Result<Actor, AppError> {
+        match self {
+            Self::Service { client_id, .. } => {
+                self.require_scope(scope)?;
+                Ok(Actor::Service(client_id.clone()))
+            }
+            Self::User { .. } => Ok(Actor::User(self.require_permission(db, permission).await?)),
+        }
+    }
+
+    // For features that widen a call instead of gating it, anything but a session has none.
+    pub async fn has_permission(&self, db: &DbConn, permission: &str) -> Result<bool, AppError> {
+        let Ok(user_id) = self.require_session() else {
+            return Ok(false);
+        };
+
+        Ok(repo::find_permissions_by_user_id(db, user_id)
+            .await?
+            .iter()
+            .any(|it| it == permission))
+    }
+}
+
+// Service claims carry a scope and must be tried first, user claims would
+// accept any token with a UUID subject.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenClaims {
+    Service(ServiceClaims),
+    User(Claims),
+}
+
+#[derive(Debug, PartialEq)]
+enum Scheme {
+    Bearer,
+    Dpop,
+}
+
+pub async fn authenticate<T>(
+    state: &AppState,
+    request: &Request<T>,
+) -> Result<Principal, AppError> {
+    let (scheme, token) = authorization(request.metadata()).ok_or(AuthError::Unauthenticated)?;
+
+    if token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX) && scheme == Scheme::Bearer {
+        return authenticate_personal_access_token(&state.db, &state.secret_key, token).await;
+    }
+
+    let principal = match (scheme, decode_jwt(&state.public_key, token)?) {
+        (Scheme::Bearer, TokenClaims::Service(claims)) => Principal::Service {
+            client_id: claims.sub,
+            scopes: claims.scope.split_whitespace().map(Into::into).collect(),
+        },
+        (Scheme::Bearer, TokenClaims::User(claims @ Claims { cnf: None, .. })) => {
+            verify_session(&state.db, claims.sub, claims.sid).await?;
+
+            Principal::User {
+                user_id: claims.sub,
+                scopes: None,
+                auth_time: claims.auth_time.map(|it| it as i64),
+            }
+        }
+        (
+            Scheme::Dpop,
+            TokenClaims::User(Claims {
+                sub,
+                sid,
+                auth_time,
+                cnf: Some(cnf),
+                ..
+            }),
+        ) => {
+            let proof = dpop::proof(request).ok_or(AuthError::InvalidDpopProof)?;
+            if dpop::verify(state, request, proof, Some(token))? != cnf.jkt {
+                return Err(AuthError::InvalidDpopProof.into());
+            }
+
+            verify_session(&state.db, sub, sid).await?;
+
+            Principal::User {
+                user_id: sub,
+                scopes: None,
+                auth_time: auth_time.map(|it| it as i64),
+            }
+        }
+        _ => return Err(AuthError::InvalidDpopProof.into()),
+    };
+
+    Ok(principal)
+}
+
+// Tokens issued before sessions were introduced carry no sid and are only
+// checked against the account itself.
+async fn verify_session(db: &DbConn, user_id: Uuid, sid: Option<Uuid>) -> Result<(), AppError> {
+    let now = Utc::now().naive_utc();
+
+    let user = match sid {
+        Some(sid) => repo::find_session_with_user(db, sid)
+            .await?
+            .filter(|(session, _)| session.user_id == user_id && session.is_active(now))
+            .and_then(|(_, user)| user),
+        None => repo::find_user_by_id(db, user_id).await?,
+    }
+    .ok_or(AuthError::Unauthenticated)?;
+
+    ensure_active(&user, now)?;
+
+    Ok(())
+}
+
+async fn authenticate_personal_access_token(
+    db: &DbConn,
+    secret_key: &[u8],
+    token: &str,
+) -> Result<Principal, AppError> {
+    let prefix = personal_access_token_prefix(token).ok_or(AuthError::Unauthenticated)?;
+    let now = Utc::now().naive_utc();
+
+    let personal_access_token = repo::find_personal_access_token_by_prefix(db, prefix)
+        .await?
+        .filter(|it| verify_secret(secret_key, token, &it.token_hash) && it.is_active(now))
+        .ok_or(AuthError::Unauthenticated)?;
+
+    let user = repo::find_user_by_id(db, personal_access_token.user_id)
+        .await?
+        .ok_or(AuthError::Unauthenticated)?;
+
+    ensure_active(&user, now)?;
+
+    if needs_touch(personal_access_token.last_used_at, now) {
+        repo::touch_personal_access_token(db, personal_access_token.id, now).await?;
+    }
+
+    Ok(Principal::User {
+        user_id: personal_access_token.user_id,
+        scopes: Some(personal_access_token.scopes()),
+        auth_time: None,
+    })
+}
+
+// last_used_at is only shown to the owner, so it is written at most once per interval
+// instead of on every request.
+fn needs_touch(last_used_at: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+    last_used_at.is_none_or(|it| now - it >= Duration::seconds(LAST_USED_AT_INTERVAL))
+}
+
+fn decode_jwt(public_key: &[u8], token: &str) -> Result<TokenClaims, AuthError> {
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.set_audience(&[audience::USER, audience::SERVICE]);
+    validation.set_required_spec_claims(&["exp", "aud", "sub"]);
+
+    let claims =
+        decode::<TokenClaims>(token, &DecodingKey::from_rsa_pem(public_key)?, &validation)?.claims;
+
+    // Either audience passes validation, it still has to be the one of the kind of claims.
+    match &claims {
+        TokenClaims::Service(ServiceClaims { aud, .. }) if aud == audience::SERVICE => Ok(claims),
+        TokenClaims::User(Claims { aud, .. }) if aud == audience::USER => Ok(claims),
+        _ => Err(AuthError::Unauthenticated),
+    }
+}
+
+pub fn personal_access_token_prefix(token: &str) -> Option<&str> {
+    token.get(..PERSONAL_ACCESS_TOKEN_PREFIX.len() + PERSONAL_ACCESS_TOKEN_LOOKUP_LEN)
+}
+
+fn authorization(metadata: &MetadataMap) -> Option<(Scheme, &str)> {
+    let value = metadata.get("authorization")?.to_str().ok()?;
+
+    match value.split_once(' ')? {
+        ("Bearer", token) => Some((Scheme::Bearer, token)),
+        ("DPoP", token) => Some((Scheme::Dpop, token)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use sea_orm::DbConn;
+    use serde_json::{json, Value};
+    use tonic::metadata::MetadataMap;
+    use uuid::Uuid;
+
+    use crate::app::{audit::Actor, auth::error::AuthError, error::AppError};
+
+    use super::{
+        audience, authorization, decode_jwt, needs_touch, permission, scope, Principal, Scheme,
+        TokenClaims,
+    };
+
+    const PRIVATE_KEY: &[u8] = include_bytes!("testdata/private_key.pem");
+    const PUBLIC_KEY: &[u8] = include_bytes!("testdata/public_key.pem");
+
+    fn sign(claims: Value) -> String {
+        encode(
+            &Header::new(Algorithm::RS256),
+            &claims,
+            &EncodingKey::from_rsa_pem(PRIVATE_KEY).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn should_touch_once_per_interval() {
+        let now = Utc::now().naive_utc();
+
+        assert!(needs_touch(None, now));
+        assert!(!needs_touch(Some(now - Duration::seconds(60)), now));
+        assert!(needs_touch(Some(now - Duration::seconds(300)), now));
+    }
+
+    #[test]
+    fn should_separate_token_audiences() {
+        let exp = Utc::now().timestamp() + 60;
+        let user = |aud: &str| json!({ "aud": aud, "sub": Uuid::now_v7(), "exp": exp });
+        let service =
+            |aud: &str| json!({ "aud": aud, "sub": "messages", "scope": "users:read", "exp": exp });
+
+        assert!(matches!(
+            decode_jwt(PUBLIC_KEY, &sign(user(audience::USER))),
+            Ok(TokenClaims::User(_))
+        ));
+        assert!(matches!(
+            decode_jwt(PUBLIC_KEY, &sign(service(audience::SERVICE))),
+            Ok(TokenClaims::Service(_))
+        ));
+        assert!(decode_jwt(PUBLIC_KEY, &sign(user(audience::SERVICE))).is_err());
+        assert!(decode_jwt(PUBLIC_KEY, &sign(service(audience::USER))).is_err());
+        assert!(decode_jwt(
+            PUBLIC_KEY,
+            &sign(json!({ "sub": Uuid::now_v7(), "exp": exp }))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn should_extract_authorization() {
+        let mut metadata = MetadataMap::new();
+        assert_eq!(authorization(&metadata), None);
+
+        metadata.insert("authorization", "Basic TOKEN".parse().unwrap());
+        assert_eq!(authorization(&metadata), None);
+
+        metadata.insert("authorization", "Bearer TOKEN".parse().unwrap());
+        assert_eq!(authorization(&metadata), Some((Scheme::Bearer, "TOKEN")));
+
+        metadata.insert("authorization", "DPoP TOKEN".parse().unwrap());
+        assert_eq!(authorization(&metadata), Some((Scheme::Dpop, "TOKEN")));
+    }
+
+    #[test]
+    fn should_require_scope() {
+        let principal = Principal::Service {
+            client_id: "messages".into(),
+            scopes: vec![scope::USERS_READ.into()],
+        };
+
+        assert!(principal.require_scope(scope::USERS_READ).is_ok());
+        assert!(principal.require_scope("users:write").is_err());
+    }
+
+    #[test]
+    fn should_require_session() {
+        let user_id = Uuid::now_v7();
+
+        let session = Principal::User {
+            user_id,
+            scopes: None,
+            auth_time: None,
+        };
+        assert_eq!(session.require_session().unwrap(), user_id);
+        assert!(session.require_scope(scope::USERS_READ).is_ok());
+
+        let personal_access_token = Principal::User {
+            user_id,
+            scopes: Some(vec![]),
+            auth_time: None,
+        };
+        assert!(personal_access_token.require_session().is_err());
+        assert!(personal_access_token
+            .require_scope(scope::USERS_READ)
+            .is_err());
+    }
+
+    #[test]
+    fn should_require_recent_auth() {
+        let principal = |auth_time| Principal::User {
+            user_id: Uuid::now_v7(),
+            scopes: None,
+            auth_time,
+        };
+        let now = Utc::now().timestamp();
+
+        assert!(principal(Some(now - 60)).require_recent_auth(300).is_ok());
+        assert!(principal(Some(now - 600)).require_recent_auth(300).is_err());
+        assert!(principal(None).require_recent_auth(300).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_deny_permissions_without_session() {
+        let db = DbConn::default();
+        let principals = [
+            Principal::Service {
+                client_id: "messages".into(),
+                scopes: vec![scope::USERS_READ.into()],
+            },
+            Principal::User {
+                user_id: Uuid::now_v7(),
+                scopes: Some(vec![scope::USERS_READ.into()]),
+                auth_time: None,
+            },
+        ];
+
+        for principal in principals {
+            assert!(!principal
+                .has_permission(&db, permission::USERS_LIST)
+                .await
+                .unwrap());
+            assert!(matches!(
+                principal
+                    .require_permission(&db, permission::USERS_LIST)
+                    .await,
+                Err(AppError::Auth(AuthError::InsufficientScope))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_check_service_clients_against_scopes() {
+        let db = DbConn::default();
+        let service = |scope: &str| Principal::Service {
+            client_id: "analytics".into(),
+            scopes: vec![scope.into()],
+        };
+
+        assert_eq!(
+            service(scope::USERS_LIST)
+                .require_scope_or_permission(&db, scope::USERS_LIST, permission::USERS_LIST)
+                .await
+                .unwrap(),
+            Actor::Service("analytics".into())
+        );
+        assert!(matches!(
+            service(scope::USERS_READ)
+                .require_scope_or_permission(&db, scope::USERS_LIST, permission::USERS_LIST)
+                .await,
+            Err(AppError::Auth(AuthError::InsufficientScope))
+        ));
+    }
+}
@@ -1,26 +1,41 @@
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, IntoActiveModel as _,
-    ModelTrait, QueryFilter, QuerySelect as _,
+    prelude::DateTime,
+    sea_query::{Expr, Func, OnConflict, Query, SimpleExpr},
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+    IntoActiveModel as _, ModelTrait, Order, QueryFilter, QueryOrder as _, QuerySelect as _,
 };
 use uuid::Uuid;
 
+pub mod data_export;
+pub mod device_code;
+pub mod email_change;
+pub mod personal_access_token;
+pub mod role;
+pub mod role_permission;
+pub mod service_client;
+pub mod session;
 pub mod user;
 pub mod user_challenge;
 pub mod user_credential;
+pub mod user_role;
 
 pub async fn find_user_by_id<T: ConnectionTrait>(
     db: &T,
     id: Uuid,
 ) -> Result<Option<user::Model>, DbErr> {
-    Ok(user::Entity::find_by_id(id).one(db).await?)
+    Ok(user::Entity::find_by_id(id)
+        .filter(user::Column::DeletedAt.is_null())
+        .one(db)
+        .await?)
 }
 
 pub async fn find_user_by_email_with_credentials<T: ConnectionTrait>(
     db: &T,
-    email: &String,
+    email: &str,
 ) -> Result<Option<(user::Model, Vec<user_credential::Model>)>, DbErr> {
     match user::Entity::find()
-        .filter(user::Column::Email.eq(email))
+        .filter(email_eq(email))
+        .filter(user::Column::DeletedAt.is_null())
         .one(db)
         .await?
     {
@@ -60,13 +75,24 @@ pub async fn find_user_credential<T: ConnectionTrait>(
     Ok(user_credential::Entity::find_by_id(id).one(db).await?)
 }
 
+pub async fn find_user_credentials_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Vec<user_credential::Model>, DbErr> {
+    user_credential::Entity::find()
+        .filter(user_credential::Column::UserId.eq(user_id))
+        .all(db)
+        .await
+}
+
+// Unique index on lower(email), see users_email_lower_idx in the migrations.
+pub const USERS_EMAIL_INDEX: &str = "users_email_lower_idx";
+
 pub async fn create_user<T: ConnectionTrait>(
     db: &T,
     model: user::Model,
 ) -> Result<user::Model, DbErr> {
-    let user = model.into_active_model().insert(db).await?;
-
-    Ok(user)
+    model.into_active_model().insert(db).await
 }
 
 pub async fn create_user_credential<T: ConnectionTrait>(
@@ -86,3 +112,574 @@ pub async fn delete_user_challengle<T: ConnectionTrait>(
 
     Ok(())
 }
+
+pub async fn find_service_client<T: ConnectionTrait>(
+    db: &T,
+    id: &String,
+) -> Result<Option<service_client::Model>, DbErr> {
+    service_client::Entity::find_by_id(id).one(db).await
+}
+
+pub async fn create_personal_access_token<T: ConnectionTrait>(
+    db: &T,
+    model: personal_access_token::Model,
+) -> Result<personal_access_token::Model, DbErr> {
+    model.into_active_model().insert(db).await
+}
+
+pub async fn find_personal_access_tokens_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Vec<personal_access_token::Model>, DbErr> {
+    personal_access_token::Entity::find()
+        .filter(personal_access_token::Column::UserId.eq(user_id))
+        .order_by(personal_access_token::Column::CreatedAt, Order::Desc)
+        .all(db)
+        .await
+}
+
+pub async fn find_personal_access_token_by_prefix<T: ConnectionTrait>(
+    db: &T,
+    prefix: &str,
+) -> Result<Option<personal_access_token::Model>, DbErr> {
+    personal_access_token::Entity::find()
+        .filter(personal_access_token::Column::Prefix.eq(prefix))
+        .one(db)
+        .await
+}
+
+pub async fn revoke_personal_access_token<T: ConnectionTrait>(
+    db: &T,
+    id: Uuid,
+    user_id: Uuid,
+    now: DateTime,
+) -> Result<bool, DbErr> {
+    let res = personal_access_token::Entity::update_many()
+        .col_expr(personal_access_token::Column::RevokedAt, Expr::value(now))
+        .col_expr(personal_access_token::Column::UpdatedAt, Expr::value(now))
+        .filter(personal_access_token::Column::Id.eq(id))
+        .filter(personal_access_token::Column::UserId.eq(user_id))
+        .filter(personal_access_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(res.rows_affected > 0)
+}
+
+pub async fn touch_personal_access_token<T: ConnectionTrait>(
+    db: &T,
+    id: Uuid,
+    now: DateTime,
+) -> Result<(), DbErr> {
+    personal_access_token::Entity::update_many()
+        .col_expr(personal_access_token::Column::LastUsedAt, Expr::value(now))
+        .filter(personal_access_token::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn create_device_code<T: ConnectionTrait>(
+    db: &T,
+    model: device_code::Model,
+) -> Result<device_code::Model, DbErr> {
+    model.into_active_model().insert(db).await
+}
+
+pub async fn find_device_code_by_hash_with_lock<T: ConnectionTrait>(
+    db: &T,
+    device_code_hash: &str,
+) -> Result<Option<device_code::Model>, DbErr> {
+    device_code::Entity::find()
+        .filter(device_code::Column::DeviceCodeHash.eq(device_code_hash))
+        .lock_exclusive()
+        .one(db)
+        .await
+}
+
+pub async fn find_device_code_by_user_code_with_lock<T: ConnectionTrait>(
+    db: &T,
+    user_code: &str,
+) -> Result<Option<device_code::Model>, DbErr> {
+    device_code::Entity::find()
+        .filter(device_code::Column::UserCode.eq(user_code))
+        .lock_exclusive()
+        .one(db)
+        .await
+}
+
+pub async fn update_device_code<T: ConnectionTrait>(
+    db: &T,
+    model: device_code::ActiveModel,
+) -> Result<device_code::Model, DbErr> {
+    model.update(db).await
+}
+
+pub async fn delete_device_code<T: ConnectionTrait>(
+    db: &T,
+    model: device_code::Model,
+) -> Result<(), DbErr> {
+    model.delete(db).await?;
+
+    Ok(())
+}
+
+pub async fn delete_expired_device_codes<T: ConnectionTrait>(
+    db: &T,
+    now: DateTime,
+) -> Result<(), DbErr> {
+    device_code::Entity::delete_many()
+        .filter(device_code::Column::ExpiresAt.lte(now))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn find_user_by_email<T: ConnectionTrait>(
+    db: &T,
+    email: &str,
+) -> Result<Option<user::Model>, DbErr> {
+    user::Entity::find()
+        .filter(email_eq(email))
+        .filter(user::Column::DeletedAt.is_null())
+        .one(db)
+        .await
+}
+
+// Deleted users are left out. Status is the effective one, so a lapsed suspension counts as
+// active.
+pub struct UserFilter {
+    pub created_after: Option<DateTime>,
+    pub created_before: Option<DateTime>,
+    pub locale: Option<String>,
+    pub status: Option<user::Status>,
+}
+
+// Ordered by (created_at, id), the keyset of users_created_at_id_idx, starting after `after`.
+pub async fn find_users_page<T: ConnectionTrait>(
+    db: &T,
+    filter: &UserFilter,
+    after: Option<(DateTime, Uuid)>,
+    limit: u64,
+    now: DateTime,
+) -> Result<Vec<user::Model>, DbErr> {
+    let mut query = user::Entity::find().filter(user::Column::DeletedAt.is_null());
+
+    if let Some(created_after) = filter.created_after {
+        query = query.filter(user::Column::CreatedAt.gte(created_after));
+    }
+    if let Some(created_before) = filter.created_before {
+        query = query.filter(user::Column::CreatedAt.lt(created_before));
+    }
+    if let Some(locale) = &filter.locale {
+        query = query.filter(user::Column::Locale.eq(locale));
+    }
+    if let Some(status) = &filter.status {
+        query = query.filter(status.condition_at(now));
+    }
+    if let Some((created_at, id)) = after {
+        query = query.filter(
+            Condition::any()
+                .add(user::Column::CreatedAt.gt(created_at))
+                .add(
+                    Condition::all()
+                        .add(user::Column::CreatedAt.eq(created_at))
+                        .add(user::Column::Id.gt(id)),
+                ),
+        );
+    }
+
+    query
+        .order_by(user::Column::CreatedAt, Order::Asc)
+        .order_by(user::Column::Id, Order::Asc)
+        .limit(limit)
+        .all(db)
+        .await
+}
+
+// Matches users_email_lower_idx, so rows stored before emails were lowercased are found too.
+fn email_eq(email: &str) -> SimpleExpr {
+    Expr::expr(Func::lower(Expr::col(user::Column::Email))).eq(email.to_lowercase())
+}
+
+pub async fn find_user_by_id_with_lock<T: ConnectionTrait>(
+    db: &T,
+    id: Uuid,
+) -> Result<Option<user::Model>, DbErr> {
+    user::Entity::find_by_id(id)
+        .filter(user::Column::DeletedAt.is_null())
+        .lock_exclusive()
+        .one(db)
+        .await
+}
+
+pub async fn update_user<T: ConnectionTrait>(
+    db: &T,
+    model: user::ActiveModel,
+) -> Result<user::Model, DbErr> {
+    model.update(db).await
+}
+
+pub async fn create_email_change<T: ConnectionTrait>(
+    db: &T,
+    model: email_change::Model,
+) -> Result<email_change::Model, DbErr> {
+    model.into_active_model().insert(db).await
+}
+
+pub async fn find_email_change_by_hash_with_lock<T: ConnectionTrait>(
+    db: &T,
+    token_hash: &str,
+) -> Result<Option<email_change::Model>, DbErr> {
+    email_change::Entity::find()
+        .filter(email_change::Column::TokenHash.eq(token_hash))
+        .lock_exclusive()
+        .one(db)
+        .await
+}
+
+pub async fn find_pending_email_change_by_email<T: ConnectionTrait>(
+    db: &T,
+    email: &str,
+    now: DateTime,
+) -> Result<Option<email_change::Model>, DbErr> {
+    email_change::Entity::find()
+        .filter(email_change::Column::Email.eq(email))
+        .filter(email_change::Column::ExpiresAt.gt(now))
+        .one(db)
+        .await
+}
+
+pub async fn delete_email_changes_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<(), DbErr> {
+    email_change::Entity::delete_many()
+        .filter(email_change::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn create_session<T: ConnectionTrait>(
+    db: &T,
+    model: session::Model,
+) -> Result<session::Model, DbErr> {
+    model.into_active_model().insert(db).await
+}
+
+pub async fn find_session_with_user<T: ConnectionTrait>(
+    db: &T,
+    id: Uuid,
+) -> Result<Option<(session::Model, Option<user::Model>)>, DbErr> {
+    session::Entity::find_by_id(id)
+        .find_also_related(user::Entity)
+        .one(db)
+        .await
+}
+
+pub async fn revoke_sessions_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+    now: DateTime,
+) -> Result<(), DbErr> {
+    session::Entity::update_many()
+        .col_expr(session::Column::RevokedAt, Expr::value(now))
+        .col_expr(session::Column::UpdatedAt, Expr::value(now))
+        .filter(session::Column::UserId.eq(user_id))
+        .filter(session::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn revoke_personal_access_tokens_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+    now: DateTime,
+) -> Result<(), DbErr> {
+    personal_access_token::Entity::update_many()
+        .col_expr(personal_access_token::Column::RevokedAt, Expr::value(now))
+        .col_expr(personal_access_token::Column::UpdatedAt, Expr::value(now))
+        .filter(personal_access_token::Column::UserId.eq(user_id))
+        .filter(personal_access_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_user_credentials_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<(), DbErr> {
+    user_credential::Entity::delete_many()
+        .filter(user_credential::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_device_codes_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<(), DbErr> {
+    device_code::Entity::delete_many()
+        .filter(device_code::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn find_user_ids_to_purge<T: ConnectionTrait>(
+    db: &T,
+    deleted_before: DateTime,
+) -> Result<Vec<Uuid>, DbErr> {
+    user::Entity::find()
+        .select_only()
+        .column(user::Column::Id)
+        .filter(user::Column::DeletedAt.lte(deleted_before))
+        .filter(user::Column::PurgedAt.is_null())
+        .lock_exclusive()
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+pub async fn find_pictures_by_user_ids<T: ConnectionTrait>(
+    db: &T,
+    user_ids: &[Uuid],
+) -> Result<Vec<(Uuid, Uuid)>, DbErr> {
+    user::Entity::find()
+        .select_only()
+        .column(user::Column::Id)
+        .column(user::Column::PictureId)
+        .filter(user::Column::Id.is_in(user_ids.to_vec()))
+        .filter(user::Column::PictureId.is_not_null())
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+// Keeps the row so ids referenced elsewhere still resolve to a placeholder.
+pub async fn purge_users<T: ConnectionTrait>(
+    db: &T,
+    user_ids: &[Uuid],
+    now: DateTime,
+) -> Result<u64, DbErr> {
+    let res = user::Entity::update_many()
+        .col_expr(user::Column::Email, Expr::value(""))
+        .col_expr(user::Column::FirstName, Expr::value(""))
+        .col_expr(user::Column::LastName, Expr::value(""))
+        .col_expr(user::Column::Locale, Expr::value(Option::<String>::None))
+        .col_expr(user::Column::Handle, Expr::value(Option::<String>::None))
+        .col_expr(user::Column::HandleKey, Expr::value(Option::<String>::None))
+        .col_expr(user::Column::PictureId, Expr::value(Option::<Uuid>::None))
+        .col_expr(
+            user::Column::PictureUrl,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(user::Column::Timezone, Expr::value(Option::<String>::None))
+        .col_expr(user::Column::PurgedAt, Expr::value(now))
+        .col_expr(user::Column::UpdatedAt, Expr::value(now))
+        .filter(user::Column::Id.is_in(user_ids.to_vec()))
+        .exec(db)
+        .await?;
+
+    Ok(res.rows_affected)
+}
+
+pub async fn delete_user_challenges_by_user_ids<T: ConnectionTrait>(
+    db: &T,
+    user_ids: &[Uuid],
+) -> Result<(), DbErr> {
+    user_challenge::Entity::delete_many()
+        .filter(user_challenge::Column::UserId.is_in(user_ids.to_vec()))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn find_sessions_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Vec<session::Model>, DbErr> {
+    session::Entity::find()
+        .filter(session::Column::UserId.eq(user_id))
+        .order_by(session::Column::Id, Order::Asc)
+        .all(db)
+        .await
+}
+
+pub async fn create_data_export<T: ConnectionTrait>(
+    db: &T,
+    model: data_export::Model,
+) -> Result<data_export::Model, DbErr> {
+    model.into_active_model().insert(db).await
+}
+
+pub async fn find_data_export<T: ConnectionTrait>(
+    db: &T,
+    id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<data_export::Model>, DbErr> {
+    data_export::Entity::find_by_id(id)
+        .filter(data_export::Column::UserId.eq(user_id))
+        .one(db)
+        .await
+}
+
+pub async fn find_pending_data_export_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Option<data_export::Model>, DbErr> {
+    data_export::Entity::find()
+        .filter(data_export::Column::UserId.eq(user_id))
+        .filter(data_export::Column::Status.eq(data_export::Status::Pending))
+        .one(db)
+        .await
+}
+
+pub async fn update_data_export<T: ConnectionTrait>(
+    db: &T,
+    model: data_export::ActiveModel,
+) -> Result<data_export::Model, DbErr> {
+    model.update(db).await
+}
+
+pub async fn delete_expired_data_exports<T: ConnectionTrait>(
+    db: &T,
+    now: DateTime,
+) -> Result<(), DbErr> {
+    data_export::Entity::delete_many()
+        .filter(data_export::Column::ExpiresAt.lte(now))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_data_exports_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<(), DbErr> {
+    data_export::Entity::delete_many()
+        .filter(data_export::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn find_role<T: ConnectionTrait>(
+    db: &T,
+    name: &str,
+) -> Result<Option<role::Model>, DbErr> {
+    role::Entity::find_by_id(name).one(db).await
+}
+
+pub async fn find_roles_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Vec<String>, DbErr> {
+    user_role::Entity::find()
+        .select_only()
+        .column(user_role::Column::Role)
+        .filter(user_role::Column::UserId.eq(user_id))
+        .order_by(user_role::Column::Role, Order::Asc)
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+pub async fn find_permissions_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Vec<String>, DbErr> {
+    role_permission::Entity::find()
+        .select_only()
+        .column(role_permission::Column::Permission)
+        .distinct()
+        .filter(
+            role_permission::Column::Role.in_subquery(
+                Query::select()
+                    .column(user_role::Column::Role)
+                    .from(user_role::Entity)
+                    .and_where(user_role::Column::UserId.eq(user_id))
+                    .to_owned(),
+            ),
+        )
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+pub async fn create_user_role<T: ConnectionTrait>(
+    db: &T,
+    model: user_role::Model,
+) -> Result<(), DbErr> {
+    user_role::Entity::insert(model.into_active_model())
+        .on_conflict(
+            OnConflict::columns([user_role::Column::UserId, user_role::Column::Role])
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_user_role<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+    role: &str,
+) -> Result<bool, DbErr> {
+    let res = user_role::Entity::delete_many()
+        .filter(user_role::Column::UserId.eq(user_id))
+        .filter(user_role::Column::Role.eq(role))
+        .exec(db)
+        .await?;
+
+    Ok(res.rows_affected > 0)
+}
+
+pub async fn delete_user_credential<T: ConnectionTrait>(
+    db: &T,
+    id: &str,
+    user_id: Uuid,
+) -> Result<bool, DbErr> {
+    let res = user_credential::Entity::delete_many()
+        .filter(user_credential::Column::Id.eq(id))
+        .filter(user_credential::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    Ok(res.rows_affected > 0)
+}
+
+pub async fn revoke_session<T: ConnectionTrait>(
+    db: &T,
+    id: Uuid,
+    user_id: Uuid,
+    now: DateTime,
+) -> Result<bool, DbErr> {
+    let res = session::Entity::update_many()
+        .col_expr(session::Column::RevokedAt, Expr::value(now))
+        .col_expr(session::Column::UpdatedAt, Expr::value(now))
+        .filter(session::Column::Id.eq(id))
+        .filter(session::Column::UserId.eq(user_id))
+        .filter(session::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(res.rows_affected > 0)
+}
@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "service_clients")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+    pub secret_hash: Option<String>,
+    pub public_key: Option<String>,
+    pub scopes: String,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl Model {
+    pub fn scopes(&self) -> Vec<String> {
+        self.scopes.split_whitespace().map(Into::into).collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,6 +1,8 @@
-use sea_orm::entity::prelude::*;
+use sea_orm::{entity::prelude::*, Condition};
 use serde::{Deserialize, Serialize};
 
+use crate::app::{color, name};
+
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "users")]
 pub struct Model {
@@ -12,33 +14,87 @@ pub struct Model {
     pub locale: Option<String>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    pub deleted_at: Option<DateTime>,
+    pub purged_at: Option<DateTime>,
+    pub status: Status,
+    pub status_reason: Option<String>,
+    pub status_until: Option<DateTime>,
+    pub handle: Option<String>,
+    pub handle_key: Option<String>,
+    pub handle_changed_at: Option<DateTime>,
+    pub preferred_color: Option<String>,
+    pub picture_id: Option<Uuid>,
+    pub picture_url: Option<String>,
+    pub timezone: Option<String>,
 }
 
-// TODO: DRY
 impl Model {
+    pub fn status_at(&self, now: DateTime) -> Status {
+        self.status.at(self.status_until, now)
+    }
+
     pub fn name(&self) -> String {
-        [self.first_name.clone(), self.last_name.clone()].join(" ")
+        name::display_name(
+            &self.first_name,
+            &self.last_name,
+            self.handle.as_deref(),
+            self.locale.as_deref(),
+        )
     }
 
     pub fn abbr(&self) -> String {
-        let mut abbr = String::from("");
+        name::initials(
+            &self.first_name,
+            &self.last_name,
+            self.handle.as_deref(),
+            self.locale.as_deref(),
+        )
+    }
 
-        if let Some(l) = self.first_name.chars().take(1).last() {
-            abbr.push(l);
-        }
+    pub fn avatar_color(&self) -> &'static color::Color {
+        color::for_user(self.id, self.preferred_color.as_deref())
+    }
+}
 
-        if let Some(l) = self.last_name.chars().take(1).last() {
-            abbr.push(l);
-        }
+#[derive(Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Text")]
+pub enum Status {
+    #[sea_orm(string_value = "active")]
+    Active,
+    #[sea_orm(string_value = "suspended")]
+    Suspended,
+    #[sea_orm(string_value = "banned")]
+    Banned,
+}
 
-        abbr
+impl Status {
+    // A suspension lapses on its own once `until` has passed.
+    pub fn at(&self, until: Option<DateTime>, now: DateTime) -> Status {
+        match (self, until) {
+            (Status::Suspended, Some(until)) if until <= now => Status::Active,
+            (status, _) => status.clone(),
+        }
     }
 
-    pub fn color(&self) -> String {
-        random_color::RandomColor::new()
-            .alpha(0.3)
-            .seed(self.id.to_string())
-            .to_hsla_string()
+    // Users whose status `at` now is this one, for filtering in SQL.
+    pub fn condition_at(&self, now: DateTime) -> Condition {
+        let lapsed = Condition::all()
+            .add(Column::Status.eq(Status::Suspended))
+            .add(Column::StatusUntil.lte(now));
+
+        match self {
+            Status::Active => Condition::any()
+                .add(Column::Status.eq(Status::Active))
+                .add(lapsed),
+            Status::Suspended => Condition::all()
+                .add(Column::Status.eq(Status::Suspended))
+                .add(
+                    Condition::any()
+                        .add(Column::StatusUntil.is_null())
+                        .add(Column::StatusUntil.gt(now)),
+                ),
+            Status::Banned => Condition::all().add(Column::Status.eq(Status::Banned)),
+        }
     }
 }
 
@@ -55,3 +111,38 @@ impl Related<super::user_credential::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use sea_orm::{DbBackend, EntityTrait as _, QueryFilter as _, QueryTrait as _};
+
+    use super::{Entity, Status};
+
+    // Each filter must select exactly the rows `at` maps to that status.
+    #[test]
+    fn should_filter_on_effective_status() {
+        let now = NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let condition = |status: Status| {
+            let sql = Entity::find()
+                .filter(status.condition_at(now))
+                .build(DbBackend::Postgres)
+                .to_string();
+
+            sql.split_once(" WHERE ").unwrap().1.to_string()
+        };
+
+        assert_eq!(
+            condition(Status::Active),
+            r#""users"."status" = 'active' OR ("users"."status" = 'suspended' AND "users"."status_until" <= '2026-01-01 00:00:00.000000')"#
+        );
+        assert_eq!(
+            condition(Status::Suspended),
+            r#""users"."status" = 'suspended' AND ("users"."status_until" IS NULL OR "users"."status_until" > '2026-01-01 00:00:00.000000')"#
+        );
+        assert_eq!(condition(Status::Banned), r#""users"."status" = 'banned'"#);
+    }
+}
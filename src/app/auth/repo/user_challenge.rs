@@ -11,22 +11,7 @@ pub struct Model {
     pub created_at: DateTime,
 }
 
-// #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-// pub enum Relation {
-//     #[sea_orm(
-//         belongs_to = "super::user::Entity",
-//         from = "Column::UserId",
-//         to = "super::user::Column::Id"
-//     )]
-//     User,
-// }
-
-// impl Related<super::user::Entity> for Entity {
-//     fn to() -> RelationDef {
-//         Relation::User.def()
-//     }
-// }
-
+// No relation to users: a registration challenge is created before its user exists.
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
@@ -13,28 +13,13 @@ pub struct Model {
     pub updated_at: DateTime,
 }
 
-// #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-// pub enum Relation {
-//     #[sea_orm(
-//         belongs_to = "super::user::Entity",
-//         from = "Column::UserId",
-//         to = "super::user::Column::Id"
-//     )]
-//     User,
-// }
-
-// impl Related<super::user::Entity> for Entity {
-//     fn to() -> RelationDef {
-//         Relation::User.def()
-//     }
-// }
-
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
-        to = "super::user::Column::Id"
+        to = "super::user::Column::Id",
+        on_delete = "Cascade"
     )]
     User,
 }
@@ -1,18 +1,33 @@
 use anyhow::Error;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
+use hmac::{Hmac, Mac as _};
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
-use sea_orm::{DbConn, Set, TransactionTrait as _};
+use sea_orm::{ConnectionTrait, DbConn, Set, SqlErr, TransactionTrait as _};
+use serde_json::json;
+use sha2::Sha256;
+use subtle::ConstantTimeEq as _;
 use url::Url;
-
-use crate::app::{auth::passkey::ClientDataType, error::AppError};
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::app::{
+    audit::{self, action},
+    auth::passkey::ClientDataType,
+    error::AppError,
+    locale,
+    mailer::{Message, SharedMailer},
+    storage::SharedStorage,
+    users::{self, picture},
+};
 
 use super::{
+    audience,
     error::AuthError,
     passkey::{ClientData, PublicKeyCredentialCreationOptions, PublicKeyCredentialRequestOptions},
     repo,
     settings::AuthSettings,
-    Claims,
+    Claims, Confirmation, ServiceClaims,
 };
 
 #[mry::mry]
@@ -22,6 +37,8 @@ pub async fn join(
     req: join::Request,
 ) -> Result<join::Response, Error> {
     let res = match repo::find_user_by_email_with_credentials(db, &req.email).await? {
+        // Suspended and banned users get a challenge like anyone else, Login turns them away
+        // once they prove who they are, so Join doesn't tell strangers about their status.
         Some((user, user_credentials)) => {
             let public_key: PublicKeyCredentialRequestOptions = (user_credentials, settings).into();
 
@@ -38,6 +55,15 @@ pub async fn join(
             public_key.into()
         }
         None => {
+            // An address reserved by another account's pending email change can't be
+            // registered until that change is confirmed or expires.
+            if repo::find_pending_email_change_by_email(db, &req.email, Utc::now().naive_utc())
+                .await?
+                .is_some()
+            {
+                return Err(AuthError::EmailUnavailable.into());
+            }
+
             let public_key: PublicKeyCredentialCreationOptions = (req, settings).into();
 
             repo::create_user_challenge(db, {
@@ -196,7 +222,7 @@ pub mod join {
 pub async fn login(
     db: &DbConn,
     settings: &AuthSettings,
-    private_key: &Vec<u8>,
+    private_key: &[u8],
     req: login::Request,
 ) -> Result<login::Response, AppError> {
     let client_data: ClientData =
@@ -222,13 +248,22 @@ pub async fn login(
         .await?
         .ok_or(AuthError::UserNotFound)?;
 
+    ensure_active(&user, Utc::now().naive_utc())?;
+
     repo::delete_user_challengle(&txn, user_challenge).await?;
 
+    let jwt = create_session(
+        &txn,
+        private_key,
+        &user,
+        Duration::days(SESSION_TTL_DAYS),
+        req.jkt,
+    )
+    .await?;
+
     txn.commit().await?;
 
-    Ok(login::Response {
-        jwt: create_jwt(&private_key, &user)?,
-    })
+    Ok(login::Response { jwt })
 }
 
 pub mod login {
@@ -247,6 +282,8 @@ pub mod login {
     #[derive(Deserialize, Validate, Debug)]
     pub struct Request {
         pub credential: PublicKeyCredentialWithAssertion,
+        #[serde(skip)]
+        pub jkt: Option<String>,
     }
 
     #[derive(Debug, Serialize)]
@@ -288,9 +325,9 @@ pub mod login {
 pub async fn complete(
     db: &DbConn,
     settings: &AuthSettings,
-    private_key: &Vec<u8>,
+    private_key: &[u8],
     req: complete::Request,
-) -> Result<complete::Response, Error> {
+) -> Result<complete::Response, AppError> {
     let client_data = req.credential.response.client_data;
 
     validate_origin(&client_data.origin, &settings.rp.id)?;
@@ -312,9 +349,36 @@ pub async fn complete(
             locale: Some(req.locale),
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
+            deleted_at: None,
+            purged_at: None,
+            status: repo::user::Status::Active,
+            status_reason: None,
+            status_until: None,
+            handle: None,
+            handle_key: None,
+            handle_changed_at: None,
+            preferred_color: None,
+            picture_id: None,
+            picture_url: None,
+            timezone: req
+                .timezone
+                .as_deref()
+                .and_then(locale::timezone)
+                .map(Into::into),
         },
     )
-    .await?;
+    .await
+    // Two Complete calls for one address race on the email index, not on the id.
+    .map_err(|err| match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(message))
+            if message.contains(repo::USERS_EMAIL_INDEX) =>
+        {
+            AuthError::EmailUnavailable.into()
+        }
+        _ => AppError::from(err),
+    })?;
+
+    audit::record(&txn, user.id, action::USER_CREATED, json!({})).await?;
 
     repo::create_user_credential(
         &txn,
@@ -331,25 +395,36 @@ pub async fn complete(
 
     repo::delete_user_challengle(&txn, user_challenge).await?;
 
+    let jwt = create_session(
+        &txn,
+        private_key,
+        &user,
+        Duration::days(SESSION_TTL_DAYS),
+        req.jkt,
+    )
+    .await?;
+
     txn.commit().await?;
 
-    Ok(complete::Response {
-        jwt: create_jwt(&private_key, &user)?,
-    })
+    Ok(complete::Response { jwt })
 }
 
 pub mod complete {
     use serde::{Deserialize, Serialize};
     use validator::Validate;
 
-    use crate::app::auth::passkey::PublicKeyCredentialWithAttestation;
+    use crate::app::{auth::passkey::PublicKeyCredentialWithAttestation, locale};
 
     #[derive(Debug, Deserialize, Validate)]
     pub struct Request {
         pub first_name: String,
         pub last_name: String,
         pub locale: String,
+        #[validate(custom(function = "locale::validate_timezone"))]
+        pub timezone: Option<String>,
         pub credential: PublicKeyCredentialWithAttestation,
+        #[serde(skip)]
+        pub jkt: Option<String>,
     }
 
     #[derive(Serialize, Debug)]
@@ -420,10 +495,439 @@ pub mod me {
     }
 }
 
-pub fn create_jwt(private_key: &Vec<u8>, user: &repo::user::Model) -> Result<String, Error> {
+pub async fn start_reauthentication(
+    db: &DbConn,
+    settings: &AuthSettings,
+    user_id: Uuid,
+) -> Result<join::Response, AppError> {
+    let user = repo::find_user_by_id(db, user_id)
+        .await?
+        .ok_or(AuthError::UserNotFound)?;
+    let user_credentials = repo::find_user_credentials_by_user_id(db, user.id).await?;
+
+    let mut public_key: PublicKeyCredentialRequestOptions = (user_credentials, settings).into();
+    public_key.user_verification = "required".into();
+
+    repo::create_user_challenge(db, {
+        repo::user_challenge::ActiveModel {
+            id: Set(URL_SAFE_NO_PAD.encode(public_key.challenge.clone())),
+            user_id: Set(user.id),
+            user_name: Set(user.email.clone()),
+            created_at: Set(Utc::now().naive_utc()),
+        }
+    })
+    .await?;
+
+    Ok(public_key.into())
+}
+
+pub async fn reauthenticate(
+    db: &DbConn,
+    settings: &AuthSettings,
+    private_key: &[u8],
+    user_id: Uuid,
+    req: login::Request,
+) -> Result<reauthenticate::Response, AppError> {
+    let client_data: ClientData =
+        serde_json::from_slice(&req.credential.response.client_data_json)?;
+
+    validate_origin(&client_data.origin, &settings.rp.id)?;
+    validate_tp(client_data.tp, ClientDataType::Get)?;
+
+    let txn = db.begin().await?;
+
+    let user_credential = repo::find_user_credential(&txn, &req.credential.id)
+        .await?
+        .filter(|it| it.user_id == user_id)
+        .ok_or(AuthError::UserCredentialNotFound)?;
+
+    let user_challenge = repo::find_user_challengle_with_lock(&txn, &client_data.challenge)
+        .await?
+        .filter(|it| it.user_id == user_id)
+        .ok_or(AuthError::UserChallengeNotFound)?;
+
+    login::verify(&req.credential.response, &user_credential.public_key)?;
+
+    if !reauthenticate::is_user_verified(&req.credential.response.authenticator_data) {
+        return Err(AuthError::UserNotVerified.into());
+    }
+
+    let user = repo::find_user_by_id(&txn, user_id)
+        .await?
+        .ok_or(AuthError::UserNotFound)?;
+
+    repo::delete_user_challengle(&txn, user_challenge).await?;
+
+    let ttl = settings.reauthentication.token_ttl;
+    let jwt = create_session(&txn, private_key, &user, Duration::seconds(ttl), req.jkt).await?;
+
+    txn.commit().await?;
+
+    Ok(reauthenticate::Response {
+        jwt,
+        expires_in: ttl,
+    })
+}
+
+pub mod reauthenticate {
+    use serde::Serialize;
+
+    // Authenticator data flags follow the 32-byte RP ID hash, UV is bit 2.
+    const FLAGS_OFFSET: usize = 32;
+    const FLAG_USER_VERIFIED: u8 = 0x04;
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub jwt: String,
+        pub expires_in: i64,
+    }
+
+    pub fn is_user_verified(authenticator_data: &[u8]) -> bool {
+        authenticator_data
+            .get(FLAGS_OFFSET)
+            .is_some_and(|flags| flags & FLAG_USER_VERIFIED != 0)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::is_user_verified;
+
+        #[test]
+        fn should_check_user_verified_flag() {
+            let mut authenticator_data = vec![0u8; 37];
+            assert!(!is_user_verified(&authenticator_data));
+
+            authenticator_data[32] = 0x01;
+            assert!(!is_user_verified(&authenticator_data));
+
+            authenticator_data[32] = 0x05;
+            assert!(is_user_verified(&authenticator_data));
+
+            assert!(!is_user_verified(&[]));
+        }
+    }
+}
+
+pub async fn start_device_authorization(
+    db: &DbConn,
+    settings: &AuthSettings,
+    secret_key: &[u8],
+    req: start_device_authorization::Request,
+) -> Result<start_device_authorization::Response, Error> {
+    let now = Utc::now().naive_utc();
+    let device_code = start_device_authorization::generate_device_code();
+    let user_code = start_device_authorization::generate_user_code();
+
+    repo::delete_expired_device_codes(db, now).await?;
+
+    repo::create_device_code(
+        db,
+        repo::device_code::Model {
+            id: Uuid::now_v7(),
+            device_code_hash: hash_secret(secret_key, &device_code),
+            user_code: user_code.clone(),
+            client_name: req.client_name,
+            status: repo::device_code::Status::Pending,
+            user_id: None,
+            interval: settings.device.interval,
+            last_polled_at: None,
+            expires_at: now + Duration::seconds(settings.device.ttl),
+            created_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+
+    let user_code = start_device_authorization::format_user_code(&user_code);
+
+    Ok(start_device_authorization::Response {
+        device_code,
+        verification_uri_complete: format!(
+            "{}?user_code={}",
+            settings.device.verification_uri, user_code
+        ),
+        verification_uri: settings.device.verification_uri.clone(),
+        user_code,
+        expires_in: settings.device.ttl,
+        interval: settings.device.interval,
+    })
+}
+
+pub mod start_device_authorization {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    use rand::{seq::IndexedRandom as _, RngCore as _};
+    use validator::Validate;
+
+    // RFC 8628 section 6.1: no vowels and no ambiguous characters.
+    const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+    const USER_CODE_LEN: usize = 8;
+
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(length(max = 100))]
+        pub client_name: Option<String>,
+    }
+
+    pub struct Response {
+        pub device_code: String,
+        pub user_code: String,
+        pub verification_uri: String,
+        pub verification_uri_complete: String,
+        pub expires_in: i64,
+        pub interval: i64,
+    }
+
+    pub fn generate_device_code() -> String {
+        let mut device_code = [0u8; 32];
+        rand::rng().fill_bytes(&mut device_code);
+
+        URL_SAFE_NO_PAD.encode(device_code)
+    }
+
+    pub fn generate_user_code() -> String {
+        let mut rng = rand::rng();
+
+        (0..USER_CODE_LEN)
+            .filter_map(|_| USER_CODE_ALPHABET.choose(&mut rng))
+            .map(|it| *it as char)
+            .collect()
+    }
+
+    pub fn format_user_code(user_code: &str) -> String {
+        let (head, tail) = user_code.split_at(USER_CODE_LEN / 2);
+
+        format!("{}-{}", head, tail)
+    }
+
+    pub fn normalize_user_code(user_code: &str) -> String {
+        user_code
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|it| it.to_ascii_uppercase())
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{
+            format_user_code, generate_user_code, normalize_user_code, USER_CODE_ALPHABET,
+            USER_CODE_LEN,
+        };
+
+        #[test]
+        fn should_generate_user_code() {
+            let user_code = generate_user_code();
+
+            assert_eq!(user_code.len(), USER_CODE_LEN);
+            assert!(user_code.bytes().all(|it| USER_CODE_ALPHABET.contains(&it)));
+        }
+
+        #[test]
+        fn should_normalize_formatted_user_code() {
+            let user_code = generate_user_code();
+            let formatted = format_user_code(&user_code);
+
+            assert_eq!(formatted.len(), USER_CODE_LEN + 1);
+            assert_eq!(normalize_user_code(&formatted), user_code);
+            assert_eq!(normalize_user_code(" wdjb-mjht "), "WDJBMJHT");
+        }
+    }
+}
+
+pub async fn poll_device_authorization(
+    db: &DbConn,
+    private_key: &[u8],
+    secret_key: &[u8],
+    req: poll_device_authorization::Request,
+) -> Result<poll_device_authorization::Response, AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let device_code =
+        repo::find_device_code_by_hash_with_lock(&txn, &hash_secret(secret_key, &req.device_code))
+            .await?
+            .ok_or(AuthError::InvalidDeviceCode)?;
+
+    if device_code.expires_at <= now {
+        repo::delete_device_code(&txn, device_code).await?;
+        txn.commit().await?;
+
+        return Err(AuthError::ExpiredToken.into());
+    }
+
+    let user_id = match (&device_code.status, device_code.user_id) {
+        (repo::device_code::Status::Approved, Some(user_id)) => user_id,
+        (repo::device_code::Status::Pending, _) => {
+            let slow_down = device_code
+                .last_polled_at
+                .is_some_and(|at| now - at < Duration::seconds(device_code.interval));
+            let interval = device_code.interval;
+
+            let mut device_code: repo::device_code::ActiveModel = device_code.into();
+            device_code.last_polled_at = Set(Some(now));
+            device_code.updated_at = Set(now);
+            if slow_down {
+                device_code.interval = Set(interval + poll_device_authorization::SLOW_DOWN_STEP);
+            }
+            repo::update_device_code(&txn, device_code).await?;
+            txn.commit().await?;
+
+            return Err(match slow_down {
+                true => AuthError::SlowDown,
+                false => AuthError::AuthorizationPending,
+            }
+            .into());
+        }
+        _ => {
+            repo::delete_device_code(&txn, device_code).await?;
+            txn.commit().await?;
+
+            return Err(AuthError::AccessDenied.into());
+        }
+    };
+
+    let user = repo::find_user_by_id(&txn, user_id)
+        .await?
+        .ok_or(AuthError::UserNotFound)?;
+
+    repo::delete_device_code(&txn, device_code).await?;
+
+    let jwt = create_session(
+        &txn,
+        private_key,
+        &user,
+        Duration::days(SESSION_TTL_DAYS),
+        req.jkt,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(poll_device_authorization::Response { jwt })
+}
+
+pub mod poll_device_authorization {
+    use validator::Validate;
+
+    // RFC 8628 section 3.5: the interval grows by 5 seconds on slow_down.
+    pub const SLOW_DOWN_STEP: i64 = 5;
+
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(length(min = 1))]
+        pub device_code: String,
+        pub jkt: Option<String>,
+    }
+
+    pub struct Response {
+        pub jwt: String,
+    }
+}
+
+pub async fn approve_device_authorization(
+    db: &DbConn,
+    user_id: Uuid,
+    req: approve_device_authorization::Request,
+) -> Result<(), AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let device_code = repo::find_device_code_by_user_code_with_lock(&txn, &req.user_code)
+        .await?
+        .filter(|it| it.status == repo::device_code::Status::Pending && it.expires_at > now)
+        .ok_or(AuthError::InvalidDeviceCode)?;
+
+    let mut device_code: repo::device_code::ActiveModel = device_code.into();
+    device_code.status = Set(match req.approve {
+        true => repo::device_code::Status::Approved,
+        false => repo::device_code::Status::Denied,
+    });
+    device_code.user_id = Set(Some(user_id));
+    device_code.updated_at = Set(now);
+    let device_code = repo::update_device_code(&txn, device_code).await?;
+
+    audit::record(
+        &txn,
+        user_id,
+        match req.approve {
+            true => action::DEVICE_APPROVED,
+            false => action::DEVICE_DENIED,
+        },
+        json!({ "client_name": device_code.client_name }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod approve_device_authorization {
+    use validator::Validate;
+
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(length(equal = 8))]
+        pub user_code: String,
+        pub approve: bool,
+    }
+}
+
+const SESSION_TTL_DAYS: i64 = 300;
+
+pub async fn create_session<T: ConnectionTrait>(
+    db: &T,
+    private_key: &[u8],
+    user: &repo::user::Model,
+    ttl: Duration,
+    jkt: Option<String>,
+) -> Result<String, AppError> {
+    let now = Utc::now().naive_utc();
+
+    let session = repo::create_session(
+        db,
+        repo::session::Model {
+            id: Uuid::now_v7(),
+            user_id: user.id,
+            jkt: jkt.clone(),
+            expires_at: now + ttl,
+            revoked_at: None,
+            created_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+
+    let roles = repo::find_roles_by_user_id(db, user.id).await?;
+
+    audit::record(
+        db,
+        user.id,
+        action::SESSION_CREATED,
+        json!({ "session_id": session.id }),
+    )
+    .await?;
+
+    Ok(create_jwt(private_key, user, session.id, roles, ttl, jkt)?)
+}
+
+pub fn create_jwt(
+    private_key: &[u8],
+    user: &repo::user::Model,
+    sid: Uuid,
+    roles: Vec<String>,
+    ttl: Duration,
+    jkt: Option<String>,
+) -> Result<String, Error> {
+    let now = Utc::now();
     let claims = Claims {
+        aud: audience::USER.into(),
         sub: user.id,
-        exp: (Utc::now() + Duration::days(300)).timestamp().try_into()?,
+        exp: (now + ttl).timestamp().try_into()?,
+        sid: Some(sid),
+        roles,
+        auth_time: Some(now.timestamp().try_into()?),
+        cnf: jkt.map(|jkt| Confirmation { jkt }),
     };
 
     let jwt = encode(
@@ -435,21 +939,1288 @@ pub fn create_jwt(private_key: &Vec<u8>, user: &repo::user::Model) -> Result<Str
     Ok(jwt)
 }
 
-fn validate_origin(origin: &str, expected: &str) -> Result<(), AuthError> {
-    let url = Url::parse(origin).map_err(AuthError::UnparsedRpId)?;
-    let host = url.host().ok_or(AuthError::InvalidRpId)?;
+pub async fn issue_service_token(
+    db: &DbConn,
+    settings: &AuthSettings,
+    private_key: &[u8],
+    secret_key: &[u8],
+    req: issue_service_token::Request,
+) -> Result<issue_service_token::Response, AppError> {
+    let service_client = repo::find_service_client(db, &req.client_id)
+        .await?
+        .ok_or(AuthError::InvalidClient)?;
 
-    if host.to_string() != expected {
-        return Err(AuthError::RpIdMissmatch);
-    };
+    issue_service_token::authenticate(
+        &service_client,
+        &req.credentials,
+        &settings.rp.id,
+        secret_key,
+    )?;
 
-    Ok(())
+    let scopes = issue_service_token::grant(&service_client.scopes(), req.scopes)?;
+
+    Ok(issue_service_token::Response {
+        token: create_service_jwt(
+            private_key,
+            &service_client,
+            &scopes,
+            settings.service_token.ttl,
+        )?,
+        expires_in: settings.service_token.ttl,
+        scopes,
+    })
 }
 
-fn validate_tp(tp: ClientDataType, expected: ClientDataType) -> Result<(), AuthError> {
-    if tp != expected {
-        return Err(AuthError::InvalidClientDataType);
+pub mod issue_service_token {
+    use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+    use serde::Deserialize;
+    use validator::Validate;
+
+    use super::verify_secret;
+    use crate::app::auth::{error::AuthError, repo};
+
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(length(min = 1))]
+        pub client_id: String,
+        pub credentials: Credentials,
+        pub scopes: Vec<String>,
     }
 
-    Ok(())
+    pub enum Credentials {
+        Secret(String),
+        Assertion(String),
+    }
+
+    pub struct Response {
+        pub token: String,
+        pub expires_in: i64,
+        pub scopes: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct AssertionClaims {
+        sub: String,
+    }
+
+    pub fn authenticate(
+        service_client: &repo::service_client::Model,
+        credentials: &Credentials,
+        audience: &str,
+        secret_key: &[u8],
+    ) -> Result<(), AuthError> {
+        match (credentials, service_client) {
+            (
+                Credentials::Secret(secret),
+                repo::service_client::Model {
+                    secret_hash: Some(secret_hash),
+                    ..
+                },
+            ) if verify_secret(secret_key, secret, secret_hash) => Ok(()),
+            (
+                Credentials::Assertion(assertion),
+                repo::service_client::Model {
+                    public_key: Some(public_key),
+                    ..
+                },
+            ) => {
+                let header = decode_header(assertion)?;
+                let key = match header.alg {
+                    Algorithm::ES256 => DecodingKey::from_ec_pem(public_key.as_bytes())?,
+                    Algorithm::RS256 => DecodingKey::from_rsa_pem(public_key.as_bytes())?,
+                    Algorithm::EdDSA => DecodingKey::from_ed_pem(public_key.as_bytes())?,
+                    _ => return Err(AuthError::InvalidClient),
+                };
+
+                let mut validation = Validation::new(header.alg);
+                validation.set_audience(&[audience]);
+                validation.set_issuer(&[&service_client.id]);
+                validation.set_required_spec_claims(&["exp", "aud", "iss", "sub"]);
+
+                let claims = decode::<AssertionClaims>(assertion, &key, &validation)?.claims;
+                if claims.sub != service_client.id {
+                    return Err(AuthError::InvalidClient);
+                }
+
+                Ok(())
+            }
+            _ => Err(AuthError::InvalidClient),
+        }
+    }
+
+    pub fn grant(allowed: &[String], requested: Vec<String>) -> Result<Vec<String>, AuthError> {
+        if requested.is_empty() {
+            return Ok(allowed.to_vec());
+        }
+
+        if requested.iter().any(|scope| !allowed.contains(scope)) {
+            return Err(AuthError::InvalidScope);
+        }
+
+        Ok(requested)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use chrono::Utc;
+
+        use super::{authenticate, grant, Credentials};
+        use crate::app::auth::{repo, service::hash_secret};
+
+        #[test]
+        fn should_authenticate_secret() {
+            let secret_key = b"secret key";
+            let service_client = repo::service_client::Model {
+                id: "messages".into(),
+                secret_hash: Some(hash_secret(secret_key, "s3cret")),
+                public_key: None,
+                scopes: "users:read".into(),
+                created_at: Utc::now().naive_utc(),
+                updated_at: Utc::now().naive_utc(),
+            };
+            let secret = |secret: &str| Credentials::Secret(secret.into());
+
+            assert!(authenticate(&service_client, &secret("s3cret"), "", secret_key).is_ok());
+            assert!(authenticate(&service_client, &secret("s3cret!"), "", secret_key).is_err());
+            assert!(authenticate(&service_client, &secret("s3cret"), "", b"other key").is_err());
+        }
+
+        #[test]
+        fn should_grant_requested_scopes() {
+            let allowed = vec!["users:read".to_string(), "users:write".to_string()];
+
+            assert_eq!(grant(&allowed, vec![]).unwrap(), allowed);
+            assert_eq!(
+                grant(&allowed, vec!["users:read".into()]).unwrap(),
+                vec!["users:read".to_string()]
+            );
+            assert!(grant(&allowed, vec!["admin".into()]).is_err());
+        }
+    }
+}
+
+pub async fn create_personal_access_token(
+    db: &DbConn,
+    secret_key: &[u8],
+    user_id: Uuid,
+    req: create_personal_access_token::Request,
+) -> Result<create_personal_access_token::Response, Error> {
+    let mut attempt = 1;
+
+    // The lookup prefix is unique, a collision is retried with a new token.
+    let (token, personal_access_token) = loop {
+        let (prefix, token) = create_personal_access_token::generate();
+
+        match repo::create_personal_access_token(
+            db,
+            repo::personal_access_token::Model {
+                id: Uuid::now_v7(),
+                user_id,
+                name: req.name.clone(),
+                prefix,
+                token_hash: hash_secret(secret_key, &token),
+                scopes: req.scopes.join(" "),
+                expires_at: req.expires_at,
+                last_used_at: None,
+                revoked_at: None,
+                created_at: Utc::now().naive_utc(),
+                updated_at: Utc::now().naive_utc(),
+            },
+        )
+        .await
+        {
+            Ok(personal_access_token) => break (token, personal_access_token),
+            Err(err)
+                if attempt < create_personal_access_token::MAX_ATTEMPTS
+                    && matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) =>
+            {
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    audit::record(
+        db,
+        user_id,
+        action::PERSONAL_ACCESS_TOKEN_CREATED,
+        json!({ "personal_access_token_id": personal_access_token.id }),
+    )
+    .await?;
+
+    Ok(create_personal_access_token::Response {
+        token,
+        personal_access_token,
+    })
+}
+
+pub mod create_personal_access_token {
+    use chrono::{NaiveDateTime, Utc};
+    use rand::RngCore as _;
+    use validator::{Validate, ValidationError};
+
+    use crate::app::auth::{
+        principal::{scope::USER_SCOPES, PERSONAL_ACCESS_TOKEN_PREFIX},
+        repo,
+    };
+
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(length(min = 1, max = 100))]
+        pub name: String,
+        #[validate(length(min = 1), custom(function = "validate_scopes"))]
+        pub scopes: Vec<String>,
+        #[validate(custom(function = "validate_expires_at"))]
+        pub expires_at: Option<NaiveDateTime>,
+    }
+
+    pub struct Response {
+        pub token: String,
+        pub personal_access_token: repo::personal_access_token::Model,
+    }
+
+    pub const MAX_ATTEMPTS: usize = 3;
+
+    pub fn generate() -> (String, String) {
+        let mut lookup = [0u8; 4];
+        rand::rng().fill_bytes(&mut lookup);
+
+        let mut secret = [0u8; 32];
+        rand::rng().fill_bytes(&mut secret);
+
+        let encode =
+            |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() };
+
+        let prefix = format!("{}{}", PERSONAL_ACCESS_TOKEN_PREFIX, encode(&lookup));
+        let token = format!("{}{}", prefix, encode(&secret));
+
+        (prefix, token)
+    }
+
+    fn validate_scopes(scopes: &[String]) -> Result<(), ValidationError> {
+        if scopes
+            .iter()
+            .any(|scope| !USER_SCOPES.contains(&scope.as_str()))
+        {
+            return Err(ValidationError::new("scopes"));
+        }
+
+        Ok(())
+    }
+
+    fn validate_expires_at(expires_at: &NaiveDateTime) -> Result<(), ValidationError> {
+        if *expires_at <= Utc::now().naive_utc() {
+            return Err(ValidationError::new("expires_at"));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::app::auth::principal::{
+            personal_access_token_prefix, PERSONAL_ACCESS_TOKEN_PREFIX,
+        };
+
+        use super::generate;
+
+        #[test]
+        fn should_generate_prefixed_token() {
+            let (prefix, token) = generate();
+
+            assert!(prefix.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX));
+            assert_eq!(personal_access_token_prefix(&token), Some(prefix.as_str()));
+            assert_eq!(token.len(), prefix.len() + 64);
+            assert_ne!(generate().1, token);
+        }
+    }
+}
+
+pub async fn list_personal_access_tokens(
+    db: &DbConn,
+    user_id: Uuid,
+) -> Result<list_personal_access_tokens::Response, Error> {
+    let personal_access_tokens = repo::find_personal_access_tokens_by_user_id(db, user_id).await?;
+
+    Ok(list_personal_access_tokens::Response {
+        personal_access_tokens,
+    })
+}
+
+pub mod list_personal_access_tokens {
+    use crate::app::auth::repo;
+
+    pub struct Response {
+        pub personal_access_tokens: Vec<repo::personal_access_token::Model>,
+    }
+}
+
+pub async fn revoke_personal_access_token(
+    db: &DbConn,
+    user_id: Uuid,
+    req: revoke_personal_access_token::Request,
+) -> Result<(), AppError> {
+    let revoked =
+        repo::revoke_personal_access_token(db, req.id, user_id, Utc::now().naive_utc()).await?;
+
+    if !revoked {
+        return Err(AppError::NotFound);
+    }
+
+    audit::record(
+        db,
+        user_id,
+        action::PERSONAL_ACCESS_TOKEN_REVOKED,
+        json!({ "personal_access_token_id": req.id }),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub mod revoke_personal_access_token {
+    use uuid::Uuid;
+
+    pub struct Request {
+        pub id: Uuid,
+    }
+}
+
+pub async fn change_email(
+    db: &DbConn,
+    settings: &AuthSettings,
+    secret_key: &[u8],
+    mailer: &SharedMailer,
+    user_id: Uuid,
+    req: change_email::Request,
+) -> Result<(), AppError> {
+    let now = Utc::now().naive_utc();
+
+    let user = repo::find_user_by_id(db, user_id)
+        .await?
+        .ok_or(AuthError::UserNotFound)?;
+
+    // Stored addresses may predate lowercasing.
+    if user.email.eq_ignore_ascii_case(&req.email) {
+        let mut errors = ValidationErrors::new();
+        errors.add("email", ValidationError::new("unchanged"));
+
+        return Err(errors.into());
+    }
+
+    if repo::find_user_by_email(db, &req.email).await?.is_some()
+        || repo::find_pending_email_change_by_email(db, &req.email, now)
+            .await?
+            .is_some_and(|it| it.user_id != user.id)
+    {
+        return Err(AuthError::EmailUnavailable.into());
+    }
+
+    let token = change_email::generate_token();
+
+    let txn = db.begin().await?;
+
+    // Only the latest request stays confirmable.
+    repo::delete_email_changes_by_user_id(&txn, user.id).await?;
+    repo::create_email_change(
+        &txn,
+        repo::email_change::Model {
+            id: Uuid::now_v7(),
+            user_id: user.id,
+            email: req.email.clone(),
+            token_hash: hash_secret(secret_key, &token),
+            expires_at: now + Duration::seconds(settings.email_change.ttl),
+            created_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+
+    audit::record(&txn, user.id, action::EMAIL_CHANGE_REQUESTED, json!({})).await?;
+
+    txn.commit().await?;
+
+    mailer
+        .send(Message {
+            to: req.email.clone(),
+            subject: "Confirm your new email address".into(),
+            body: format!(
+                "Follow the link to confirm {} as your new email address:\n{}?token={}",
+                req.email, settings.email_change.confirmation_uri, token
+            ),
+        })
+        .await?;
+
+    mailer
+        .send(Message {
+            to: user.email,
+            subject: "Your email address is being changed".into(),
+            body: format!(
+                "A change of your account email to {} was requested. \
+                 If this was not you, do not confirm it and secure your account.",
+                req.email
+            ),
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub mod change_email {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    use rand::RngCore as _;
+    use validator::Validate;
+
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(email)]
+        pub email: String,
+    }
+
+    pub fn generate_token() -> String {
+        let mut token = [0u8; 32];
+        rand::rng().fill_bytes(&mut token);
+
+        URL_SAFE_NO_PAD.encode(token)
+    }
+}
+
+pub async fn confirm_email_change(
+    db: &DbConn,
+    secret_key: &[u8],
+    req: confirm_email_change::Request,
+) -> Result<(), AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let email_change =
+        repo::find_email_change_by_hash_with_lock(&txn, &hash_secret(secret_key, &req.token))
+            .await?
+            .ok_or(AuthError::InvalidEmailChangeToken)?;
+
+    if email_change.expires_at <= now {
+        return Err(AuthError::ExpiredToken.into());
+    }
+
+    // The address could have been registered while the change was pending.
+    if repo::find_user_by_email(&txn, &email_change.email)
+        .await?
+        .is_some()
+    {
+        return Err(AuthError::EmailUnavailable.into());
+    }
+
+    let user = repo::find_user_by_id_with_lock(&txn, email_change.user_id)
+        .await?
+        .ok_or(AuthError::UserNotFound)?;
+
+    let mut user: repo::user::ActiveModel = user.into();
+    user.email = Set(email_change.email);
+    user.updated_at = Set(now);
+    repo::update_user(&txn, user)
+        .await
+        // The check above takes no lock, a concurrent Complete or confirmation can still win.
+        .map_err(|err| match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(message))
+                if message.contains(repo::USERS_EMAIL_INDEX) =>
+            {
+                AuthError::EmailUnavailable.into()
+            }
+            _ => AppError::from(err),
+        })?;
+
+    repo::delete_email_changes_by_user_id(&txn, email_change.user_id).await?;
+
+    audit::record(&txn, email_change.user_id, action::EMAIL_CHANGED, json!({})).await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod confirm_email_change {
+    use validator::Validate;
+
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(length(min = 1))]
+        pub token: String,
+    }
+}
+
+pub async fn delete_account(
+    db: &DbConn,
+    storage: &SharedStorage,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let user = repo::find_user_by_id_with_lock(&txn, user_id)
+        .await?
+        .ok_or(AuthError::UserNotFound)?;
+    let picture_id = user.picture_id;
+
+    // The picture goes right away rather than at purge, its URL is public and guessable
+    // by anyone who saw it before.
+    let mut user: repo::user::ActiveModel = user.into();
+    user.deleted_at = Set(Some(now));
+    user.picture_id = Set(None);
+    user.picture_url = Set(None);
+    user.updated_at = Set(now);
+    repo::update_user(&txn, user).await?;
+    users::watch::notify(&txn, user_id).await?;
+
+    repo::delete_user_credentials_by_user_id(&txn, user_id).await?;
+    repo::revoke_sessions_by_user_id(&txn, user_id, now).await?;
+    repo::revoke_personal_access_tokens_by_user_id(&txn, user_id, now).await?;
+    repo::delete_device_codes_by_user_id(&txn, user_id).await?;
+    repo::delete_email_changes_by_user_id(&txn, user_id).await?;
+    repo::delete_data_exports_by_user_id(&txn, user_id).await?;
+
+    audit::record(&txn, user_id, action::ACCOUNT_DELETED, json!({})).await?;
+
+    txn.commit().await?;
+
+    if let Some(picture_id) = picture_id {
+        picture::delete(storage.as_ref(), user_id, picture_id).await;
+    }
+
+    Ok(())
+}
+
+pub async fn purge_deleted_users(
+    db: &DbConn,
+    storage: &SharedStorage,
+    settings: &AuthSettings,
+) -> Result<u64, AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let user_ids = repo::find_user_ids_to_purge(
+        &txn,
+        now - Duration::seconds(settings.deletion.grace_period),
+    )
+    .await?;
+
+    if user_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let pictures = repo::find_pictures_by_user_ids(&txn, &user_ids).await?;
+
+    repo::delete_user_challenges_by_user_ids(&txn, &user_ids).await?;
+    users::repo::delete_user_preferences_by_user_ids(&txn, &user_ids).await?;
+    let purged = repo::purge_users(&txn, &user_ids, now).await?;
+    for user_id in &user_ids {
+        users::watch::notify(&txn, *user_id).await?;
+    }
+
+    txn.commit().await?;
+
+    for (user_id, picture_id) in pictures {
+        picture::delete(storage.as_ref(), user_id, picture_id).await;
+    }
+
+    Ok(purged)
+}
+
+pub async fn export_my_data(
+    db: &DbConn,
+    settings: &AuthSettings,
+    user_id: Uuid,
+) -> Result<export_my_data::Response, AppError> {
+    let now = Utc::now().naive_utc();
+
+    repo::delete_expired_data_exports(db, now).await?;
+
+    if let Some(data_export) = repo::find_pending_data_export_by_user_id(db, user_id).await? {
+        if !data_export.is_stale(now, settings.data_export.timeout) {
+            return Ok(export_my_data::Response { data_export });
+        }
+
+        repo::update_data_export(
+            db,
+            export_my_data::finish(
+                data_export.id,
+                Err(anyhow::anyhow!("data export timed out")),
+                now,
+            ),
+        )
+        .await?;
+    }
+
+    let data_export = repo::create_data_export(
+        db,
+        repo::data_export::Model {
+            id: Uuid::now_v7(),
+            user_id,
+            status: repo::data_export::Status::Pending,
+            data: None,
+            expires_at: now + Duration::seconds(settings.data_export.ttl),
+            created_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+
+    audit::record(
+        db,
+        user_id,
+        action::DATA_EXPORT_REQUESTED,
+        json!({ "data_export_id": data_export.id }),
+    )
+    .await?;
+
+    // Large accounts take a while to collect, so the document is built in the background.
+    tokio::spawn(export_my_data::generate(
+        db.clone(),
+        data_export.id,
+        user_id,
+    ));
+
+    Ok(export_my_data::Response { data_export })
+}
+
+pub mod export_my_data {
+    use anyhow::Error;
+    use chrono::{NaiveDateTime, Utc};
+    use log::error;
+    use sea_orm::{DbConn, Set};
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    use crate::app::{audit, auth::repo, users};
+
+    pub const VERSION: u32 = 1;
+
+    pub struct Response {
+        pub data_export: repo::data_export::Model,
+    }
+
+    #[derive(Serialize)]
+    pub struct Document {
+        pub version: u32,
+        pub generated_at: NaiveDateTime,
+        pub profile: Profile,
+        pub preferences: Option<serde_json::Value>,
+        pub credentials: Vec<Credential>,
+        pub personal_access_tokens: Vec<PersonalAccessToken>,
+        pub sessions: Vec<Session>,
+        pub audit_events: Vec<AuditEvent>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Profile {
+        pub id: Uuid,
+        pub email: String,
+        pub first_name: String,
+        pub last_name: String,
+        pub locale: Option<String>,
+        pub handle: Option<String>,
+        pub preferred_color: Option<String>,
+        pub picture_url: Option<String>,
+        pub timezone: Option<String>,
+        pub created_at: NaiveDateTime,
+        pub updated_at: NaiveDateTime,
+    }
+
+    // Public keys and token hashes are left out on purpose.
+    #[derive(Serialize)]
+    pub struct Credential {
+        pub id: String,
+        pub public_key_algorithm: i32,
+        pub created_at: NaiveDateTime,
+    }
+
+    #[derive(Serialize)]
+    pub struct PersonalAccessToken {
+        pub id: Uuid,
+        pub name: String,
+        pub prefix: String,
+        pub scopes: Vec<String>,
+        pub created_at: NaiveDateTime,
+        pub expires_at: Option<NaiveDateTime>,
+        pub last_used_at: Option<NaiveDateTime>,
+        pub revoked_at: Option<NaiveDateTime>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Session {
+        pub id: Uuid,
+        pub dpop_bound: bool,
+        pub created_at: NaiveDateTime,
+        pub expires_at: NaiveDateTime,
+        pub revoked_at: Option<NaiveDateTime>,
+    }
+
+    #[derive(Serialize)]
+    pub struct AuditEvent {
+        pub action: String,
+        pub metadata: serde_json::Value,
+        pub created_at: NaiveDateTime,
+    }
+
+    pub async fn generate(db: DbConn, id: Uuid, user_id: Uuid) {
+        let data = collect(&db, user_id)
+            .await
+            .and_then(|document| Ok(serde_json::to_value(document)?));
+
+        let data_export = finish(id, data, Utc::now().naive_utc());
+
+        if let Err(err) = repo::update_data_export(&db, data_export).await {
+            error!("auth: data export {} failed: {}", id, err);
+        }
+    }
+
+    pub fn finish(
+        id: Uuid,
+        data: Result<serde_json::Value, Error>,
+        now: NaiveDateTime,
+    ) -> repo::data_export::ActiveModel {
+        let (status, data) = match data {
+            Ok(data) => (repo::data_export::Status::Ready, Some(data)),
+            Err(err) => {
+                error!("auth: data export {} failed: {}", id, err);
+
+                (repo::data_export::Status::Failed, None)
+            }
+        };
+
+        repo::data_export::ActiveModel {
+            id: Set(id),
+            status: Set(status),
+            data: Set(data),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+    }
+
+    async fn collect(db: &DbConn, user_id: Uuid) -> Result<Document, Error> {
+        let user = repo::find_user_by_id(db, user_id)
+            .await?
+            .ok_or(anyhow::anyhow!("user {} not found", user_id))?;
+
+        Ok(Document {
+            version: VERSION,
+            generated_at: Utc::now().naive_utc(),
+            profile: user.into(),
+            preferences: users::repo::find_user_preference_by_user_id(db, user_id)
+                .await?
+                .map(|it| it.data),
+            credentials: repo::find_user_credentials_by_user_id(db, user_id)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            personal_access_tokens: repo::find_personal_access_tokens_by_user_id(db, user_id)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            sessions: repo::find_sessions_by_user_id(db, user_id)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            audit_events: audit::repo::find_audit_events_by_user_id(db, user_id)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        })
+    }
+
+    impl From<repo::user::Model> for Profile {
+        fn from(user: repo::user::Model) -> Self {
+            Self {
+                id: user.id,
+                email: user.email,
+                first_name: user.first_name,
+                last_name: user.last_name,
+                locale: user.locale,
+                handle: user.handle,
+                preferred_color: user.preferred_color,
+                picture_url: user.picture_url,
+                timezone: user.timezone,
+                created_at: user.created_at,
+                updated_at: user.updated_at,
+            }
+        }
+    }
+
+    impl From<repo::user_credential::Model> for Credential {
+        fn from(user_credential: repo::user_credential::Model) -> Self {
+            Self {
+                id: user_credential.id,
+                public_key_algorithm: user_credential.public_key_algorithm,
+                created_at: user_credential.created_at,
+            }
+        }
+    }
+
+    impl From<repo::personal_access_token::Model> for PersonalAccessToken {
+        fn from(personal_access_token: repo::personal_access_token::Model) -> Self {
+            Self {
+                id: personal_access_token.id,
+                scopes: personal_access_token.scopes(),
+                name: personal_access_token.name,
+                prefix: personal_access_token.prefix,
+                created_at: personal_access_token.created_at,
+                expires_at: personal_access_token.expires_at,
+                last_used_at: personal_access_token.last_used_at,
+                revoked_at: personal_access_token.revoked_at,
+            }
+        }
+    }
+
+    impl From<repo::session::Model> for Session {
+        fn from(session: repo::session::Model) -> Self {
+            Self {
+                id: session.id,
+                dpop_bound: session.jkt.is_some(),
+                created_at: session.created_at,
+                expires_at: session.expires_at,
+                revoked_at: session.revoked_at,
+            }
+        }
+    }
+
+    impl From<audit::repo::audit_event::Model> for AuditEvent {
+        fn from(audit_event: audit::repo::audit_event::Model) -> Self {
+            Self {
+                action: audit_event.action,
+                metadata: audit_event.metadata,
+                created_at: audit_event.created_at,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use chrono::{Duration, Utc};
+        use sea_orm::ActiveValue;
+        use serde_json::json;
+        use uuid::Uuid;
+
+        use crate::app::auth::repo::{self, data_export::Status};
+
+        use super::{finish, Credential, PersonalAccessToken, Session};
+
+        fn data_export(
+            status: Status,
+            updated_at: chrono::NaiveDateTime,
+        ) -> repo::data_export::Model {
+            repo::data_export::Model {
+                id: Uuid::now_v7(),
+                user_id: Uuid::now_v7(),
+                status,
+                data: None,
+                expires_at: updated_at + Duration::days(7),
+                created_at: updated_at,
+                updated_at,
+            }
+        }
+
+        #[test]
+        fn should_expire_stale_pending_exports() {
+            let now = Utc::now().naive_utc();
+
+            assert!(!data_export(Status::Pending, now - Duration::seconds(59)).is_stale(now, 60));
+            assert!(data_export(Status::Pending, now - Duration::seconds(60)).is_stale(now, 60));
+            assert!(!data_export(Status::Ready, now - Duration::hours(1)).is_stale(now, 60));
+            assert!(!data_export(Status::Failed, now - Duration::hours(1)).is_stale(now, 60));
+        }
+
+        #[test]
+        fn should_finish_ready_with_data() {
+            let data_export = finish(
+                Uuid::now_v7(),
+                Ok(json!({ "version": 1 })),
+                Utc::now().naive_utc(),
+            );
+
+            assert_eq!(data_export.status, ActiveValue::Set(Status::Ready));
+            assert_eq!(
+                data_export.data,
+                ActiveValue::Set(Some(json!({ "version": 1 })))
+            );
+        }
+
+        #[test]
+        fn should_finish_failed_without_data() {
+            let data_export = finish(
+                Uuid::now_v7(),
+                Err(anyhow::anyhow!("boom")),
+                Utc::now().naive_utc(),
+            );
+
+            assert_eq!(data_export.status, ActiveValue::Set(Status::Failed));
+            assert_eq!(data_export.data, ActiveValue::Set(None));
+        }
+
+        #[test]
+        fn should_omit_token_hashes() {
+            let personal_access_token: PersonalAccessToken = repo::personal_access_token::Model {
+                id: Uuid::now_v7(),
+                user_id: Uuid::now_v7(),
+                name: "CI".into(),
+                prefix: "flux_pat_abcd".into(),
+                token_hash: "HASH".into(),
+                scopes: "users:read users:write".into(),
+                expires_at: None,
+                last_used_at: None,
+                revoked_at: None,
+                created_at: Utc::now().naive_utc(),
+                updated_at: Utc::now().naive_utc(),
+            }
+            .into();
+
+            let value = serde_json::to_value(personal_access_token).unwrap();
+            assert_eq!(value["scopes"], json!(["users:read", "users:write"]));
+            assert!(value.get("token_hash").is_none());
+        }
+
+        #[test]
+        fn should_export_session_binding_without_thumbprint() {
+            let session: Session = repo::session::Model {
+                id: Uuid::now_v7(),
+                user_id: Uuid::now_v7(),
+                jkt: Some("JKT".into()),
+                expires_at: Utc::now().naive_utc(),
+                revoked_at: None,
+                created_at: Utc::now().naive_utc(),
+                updated_at: Utc::now().naive_utc(),
+            }
+            .into();
+
+            let value = serde_json::to_value(session).unwrap();
+            assert_eq!(value["dpop_bound"], true);
+            assert!(value.get("jkt").is_none());
+        }
+
+        #[test]
+        fn should_omit_credential_secrets() {
+            let credential: Credential = repo::user_credential::Model {
+                id: "ID".into(),
+                user_id: Uuid::now_v7(),
+                public_key: vec![1, 2, 3],
+                public_key_algorithm: -7,
+                created_at: Utc::now().naive_utc(),
+                updated_at: Utc::now().naive_utc(),
+            }
+            .into();
+
+            let value = serde_json::to_value(credential).unwrap();
+            assert_eq!(value["id"], "ID");
+            assert!(value.get("public_key").is_none());
+        }
+    }
+}
+
+pub async fn get_data_export(
+    db: &DbConn,
+    settings: &AuthSettings,
+    user_id: Uuid,
+    req: get_data_export::Request,
+) -> Result<get_data_export::Response, AppError> {
+    let now = Utc::now().naive_utc();
+
+    let mut data_export = repo::find_data_export(db, req.id, user_id)
+        .await?
+        .filter(|it| it.expires_at > now)
+        .ok_or(AppError::NotFound)?;
+
+    // The row itself is marked failed by the next ExportMyData.
+    if data_export.is_stale(now, settings.data_export.timeout) {
+        data_export.status = repo::data_export::Status::Failed;
+    }
+
+    Ok(get_data_export::Response { data_export })
+}
+
+pub mod get_data_export {
+    use uuid::Uuid;
+
+    use crate::app::auth::repo;
+
+    pub struct Request {
+        pub id: Uuid,
+    }
+
+    pub struct Response {
+        pub data_export: repo::data_export::Model,
+    }
+}
+
+pub async fn grant_role(
+    db: &DbConn,
+    actor_id: Uuid,
+    req: grant_role::Request,
+) -> Result<(), AppError> {
+    let txn = db.begin().await?;
+
+    repo::find_role(&txn, &req.role)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    repo::find_user_by_id(&txn, req.user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let now = Utc::now().naive_utc();
+    repo::create_user_role(
+        &txn,
+        repo::user_role::Model {
+            user_id: req.user_id,
+            role: req.role.clone(),
+            created_at: now,
+            updated_at: now,
+        },
+    )
+    .await?;
+
+    audit::record_as(
+        &txn,
+        actor_id,
+        req.user_id,
+        action::ROLE_GRANTED,
+        json!({ "role": req.role }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod grant_role {
+    use uuid::Uuid;
+    use validator::Validate;
+
+    #[derive(Validate)]
+    pub struct Request {
+        pub user_id: Uuid,
+        #[validate(length(min = 1, max = 100))]
+        pub role: String,
+    }
+}
+
+pub async fn revoke_role(
+    db: &DbConn,
+    actor_id: Uuid,
+    req: revoke_role::Request,
+) -> Result<(), AppError> {
+    let txn = db.begin().await?;
+
+    if !repo::delete_user_role(&txn, req.user_id, &req.role).await? {
+        return Err(AppError::NotFound);
+    }
+
+    audit::record_as(
+        &txn,
+        actor_id,
+        req.user_id,
+        action::ROLE_REVOKED,
+        json!({ "role": req.role }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub mod revoke_role {
+    use uuid::Uuid;
+    use validator::Validate;
+
+    #[derive(Validate)]
+    pub struct Request {
+        pub user_id: Uuid,
+        #[validate(length(min = 1, max = 100))]
+        pub role: String,
+    }
+}
+
+pub fn ensure_active(user: &repo::user::Model, now: NaiveDateTime) -> Result<(), AuthError> {
+    match user.status_at(now) {
+        repo::user::Status::Active => Ok(()),
+        repo::user::Status::Suspended => Err(AuthError::UserSuspended(user.status_until)),
+        repo::user::Status::Banned => Err(AuthError::UserBanned),
+    }
+}
+
+// Keyed, so a leaked table of hashes can't be checked offline without the secret key too.
+pub fn hash_secret(secret_key: &[u8], secret: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key).expect("HMAC takes keys of any size");
+    mac.update(secret.as_bytes());
+
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+pub fn verify_secret(secret_key: &[u8], secret: &str, secret_hash: &str) -> bool {
+    hash_secret(secret_key, secret)
+        .as_bytes()
+        .ct_eq(secret_hash.as_bytes())
+        .into()
+}
+
+pub fn create_service_jwt(
+    private_key: &[u8],
+    service_client: &repo::service_client::Model,
+    scopes: &[String],
+    ttl: i64,
+) -> Result<String, Error> {
+    let claims = ServiceClaims {
+        aud: audience::SERVICE.into(),
+        sub: service_client.id.clone(),
+        scope: scopes.join(" "),
+        exp: (Utc::now() + Duration::seconds(ttl))
+            .timestamp()
+            .try_into()?,
+    };
+
+    let jwt = encode(
+        &Header::new(Algorithm::RS256),
+        &claims,
+        &EncodingKey::from_rsa_pem(private_key)?,
+    )?;
+
+    Ok(jwt)
+}
+
+fn validate_origin(origin: &str, expected: &str) -> Result<(), AuthError> {
+    let url = Url::parse(origin).map_err(AuthError::UnparsedRpId)?;
+    let host = url.host().ok_or(AuthError::InvalidRpId)?;
+
+    if host.to_string() != expected {
+        return Err(AuthError::RpIdMissmatch);
+    };
+
+    Ok(())
+}
+
+fn validate_tp(tp: ClientDataType, expected: ClientDataType) -> Result<(), AuthError> {
+    if tp != expected {
+        return Err(AuthError::InvalidClientDataType);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime, Utc};
+    use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+    use uuid::Uuid;
+
+    use crate::app::auth::{
+        audience,
+        error::AuthError,
+        repo::{self, user::Status},
+        Claims,
+    };
+
+    use super::{create_jwt, ensure_active};
+
+    const PRIVATE_KEY: &[u8] = include_bytes!("testdata/private_key.pem");
+    const PUBLIC_KEY: &[u8] = include_bytes!("testdata/public_key.pem");
+
+    fn user(status: Status, status_until: Option<NaiveDateTime>) -> repo::user::Model {
+        let now = Utc::now().naive_utc();
+
+        repo::user::Model {
+            id: Uuid::now_v7(),
+            email: "user@example.com".into(),
+            first_name: "First".into(),
+            last_name: "Last".into(),
+            locale: None,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            purged_at: None,
+            status,
+            status_reason: None,
+            status_until,
+            handle: None,
+            handle_key: None,
+            handle_changed_at: None,
+            preferred_color: None,
+            picture_id: None,
+            picture_url: None,
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn should_lapse_timed_suspensions() {
+        let now = Utc::now().naive_utc();
+
+        let lapsed = user(Status::Suspended, Some(now - Duration::seconds(1)));
+        assert_eq!(lapsed.status_at(now), Status::Active);
+        assert!(ensure_active(&lapsed, now).is_ok());
+
+        let until = now + Duration::days(1);
+        let suspended = user(Status::Suspended, Some(until));
+        assert_eq!(suspended.status_at(now), Status::Suspended);
+        assert!(matches!(
+            ensure_active(&suspended, now),
+            Err(AuthError::UserSuspended(Some(it))) if it == until
+        ));
+    }
+
+    #[test]
+    fn should_keep_indefinite_suspensions() {
+        let now = Utc::now().naive_utc();
+        let suspended = user(Status::Suspended, None);
+
+        assert_eq!(
+            suspended.status_at(now + Duration::days(365)),
+            Status::Suspended
+        );
+        assert!(matches!(
+            ensure_active(&suspended, now),
+            Err(AuthError::UserSuspended(None))
+        ));
+    }
+
+    #[test]
+    fn should_reject_banned_users() {
+        let now = Utc::now().naive_utc();
+        let banned = user(Status::Banned, Some(now - Duration::days(1)));
+
+        assert_eq!(banned.status_at(now), Status::Banned);
+        assert!(matches!(
+            ensure_active(&banned, now),
+            Err(AuthError::UserBanned)
+        ));
+        assert!(ensure_active(&user(Status::Active, None), now).is_ok());
+    }
+
+    #[test]
+    fn should_carry_roles_claim() {
+        let user = user(Status::Active, None);
+        let token = create_jwt(
+            PRIVATE_KEY,
+            &user,
+            Uuid::now_v7(),
+            vec!["admin".into()],
+            Duration::minutes(5),
+            None,
+        )
+        .unwrap();
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[audience::USER]);
+        let claims = decode::<Claims>(
+            &token,
+            &DecodingKey::from_rsa_pem(PUBLIC_KEY).unwrap(),
+            &validation,
+        )
+        .unwrap()
+        .claims;
+
+        assert_eq!(claims.sub, user.id);
+        assert_eq!(claims.roles, vec!["admin".to_string()]);
+    }
 }
@@ -4,6 +4,17 @@ use serde::Deserialize;
 pub struct AuthSettings {
     pub rp: RPSettings,
     pub private_key_file: String,
+    pub public_key_file: String,
+    // Random bytes keying the hashes of stored secrets such as personal access tokens and
+    // client secrets. Rotating it invalidates all of them.
+    pub secret_key_file: String,
+    pub service_token: ServiceTokenSettings,
+    pub dpop: DpopSettings,
+    pub reauthentication: ReauthenticationSettings,
+    pub device: DeviceSettings,
+    pub email_change: EmailChangeSettings,
+    pub deletion: DeletionSettings,
+    pub data_export: DataExportSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -11,3 +22,46 @@ pub struct RPSettings {
     pub id: String,
     pub name: String,
 }
+
+#[derive(Deserialize, Clone)]
+pub struct ServiceTokenSettings {
+    pub ttl: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DpopSettings {
+    pub origin: String,
+    pub max_age: i64,
+    pub nonce_ttl: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ReauthenticationSettings {
+    pub max_age: i64,
+    pub token_ttl: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DeviceSettings {
+    pub verification_uri: String,
+    pub ttl: i64,
+    pub interval: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EmailChangeSettings {
+    pub confirmation_uri: String,
+    pub ttl: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DeletionSettings {
+    pub grace_period: i64,
+    pub purge_interval: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DataExportSettings {
+    pub ttl: i64,
+    pub timeout: i64,
+}
@@ -0,0 +1,173 @@
+use uuid::Uuid;
+
+pub struct Color {
+    pub name: &'static str,
+    pub light: Rgb,
+    pub dark: Rgb,
+}
+
+#[derive(Clone, Copy)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+// Every shade keeps a WCAG contrast ratio of at least 4.5:1 against white initials. The order
+// is part of the mapping from user ids, append new colors instead of reordering.
+pub const PALETTE: &[Color] = &[
+    Color {
+        name: "red",
+        light: Rgb(0xb9, 0x1c, 0x1c),
+        dark: Rgb(0x99, 0x1b, 0x1b),
+    },
+    Color {
+        name: "orange",
+        light: Rgb(0xc2, 0x41, 0x0c),
+        dark: Rgb(0x9a, 0x34, 0x12),
+    },
+    Color {
+        name: "amber",
+        light: Rgb(0xb4, 0x53, 0x09),
+        dark: Rgb(0x92, 0x40, 0x0e),
+    },
+    Color {
+        name: "green",
+        light: Rgb(0x15, 0x80, 0x3d),
+        dark: Rgb(0x16, 0x65, 0x34),
+    },
+    Color {
+        name: "teal",
+        light: Rgb(0x0f, 0x76, 0x6e),
+        dark: Rgb(0x11, 0x5e, 0x59),
+    },
+    Color {
+        name: "cyan",
+        light: Rgb(0x0e, 0x74, 0x90),
+        dark: Rgb(0x15, 0x5e, 0x75),
+    },
+    Color {
+        name: "blue",
+        light: Rgb(0x1d, 0x4e, 0xd8),
+        dark: Rgb(0x1e, 0x40, 0xaf),
+    },
+    Color {
+        name: "indigo",
+        light: Rgb(0x43, 0x38, 0xca),
+        dark: Rgb(0x37, 0x30, 0xa3),
+    },
+    Color {
+        name: "violet",
+        light: Rgb(0x6d, 0x28, 0xd9),
+        dark: Rgb(0x5b, 0x21, 0xb6),
+    },
+    Color {
+        name: "purple",
+        light: Rgb(0x7e, 0x22, 0xce),
+        dark: Rgb(0x6b, 0x21, 0xa8),
+    },
+    Color {
+        name: "pink",
+        light: Rgb(0xbe, 0x18, 0x5d),
+        dark: Rgb(0x9d, 0x17, 0x4d),
+    },
+    Color {
+        name: "slate",
+        light: Rgb(0x47, 0x55, 0x69),
+        dark: Rgb(0x33, 0x41, 0x55),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Color> {
+    PALETTE.iter().find(|it| it.name == name)
+}
+
+// FNV-1a over the id bytes, which unlike std's hasher is stable across releases.
+pub fn for_user(id: Uuid, preferred: Option<&str>) -> &'static Color {
+    if let Some(color) = preferred.and_then(find) {
+        return color;
+    }
+
+    let hash = id.as_bytes().iter().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    });
+
+    &PALETTE[(hash % PALETTE.len() as u64) as usize]
+}
+
+impl Rgb {
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    pub fn hsla(&self) -> String {
+        let [r, g, b] = [self.0, self.1, self.2].map(|it| f64::from(it) / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+        let d = max - min;
+
+        let (h, s) = if d == 0.0 {
+            (0.0, 0.0)
+        } else {
+            let h = if max == r {
+                ((g - b) / d).rem_euclid(6.0)
+            } else if max == g {
+                (b - r) / d + 2.0
+            } else {
+                (r - g) / d + 4.0
+            };
+
+            (h * 60.0, d / (1.0 - (2.0 * l - 1.0).abs()))
+        };
+
+        format!(
+            "hsla({}, {}%, {}%, 1)",
+            h.round(),
+            (s * 100.0).round(),
+            (l * 100.0).round()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{for_user, Rgb, PALETTE};
+
+    fn contrast_with_white(rgb: Rgb) -> f64 {
+        let channel = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let luminance = 0.2126 * channel(rgb.0) + 0.7152 * channel(rgb.1) + 0.0722 * channel(rgb.2);
+
+        1.05 / (luminance + 0.05)
+    }
+
+    #[test]
+    fn should_contrast_with_white() {
+        for color in PALETTE {
+            assert!(contrast_with_white(color.light) >= 4.5, "{}", color.name);
+            assert!(contrast_with_white(color.dark) >= 4.5, "{}", color.name);
+        }
+    }
+
+    #[test]
+    fn should_be_deterministic() {
+        let id = Uuid::now_v7();
+
+        assert_eq!(for_user(id, None).name, for_user(id, None).name);
+        assert_eq!(for_user(id, Some("teal")).name, "teal");
+        assert_eq!(for_user(id, Some("mauve")).name, for_user(id, None).name);
+    }
+
+    #[test]
+    fn should_format_css_colors() {
+        let rgb = Rgb(0x0f, 0x76, 0x6e);
+
+        assert_eq!(rgb.hex(), "#0f766e");
+        assert_eq!(rgb.hsla(), "hsla(175, 77%, 26%, 1)");
+    }
+}
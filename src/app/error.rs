@@ -1,27 +1,68 @@
-use axum::body::Bytes;
 use thiserror::Error;
-use tonic::{metadata::MetadataMap, Code, Status};
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, FieldViolation, StatusExt as _};
+use validator::{ValidationErrors, ValidationErrorsKind};
 
-use super::auth;
+use super::{auth, users};
 
 impl From<AppError> for Status {
     fn from(error: AppError) -> Self {
         match error {
-            AppError::Validation(validation_errors) => Self::with_details_and_metadata(
+            AppError::Validation(validation_errors) => Self::with_error_details(
                 Code::InvalidArgument,
                 validation_errors.to_string(),
-                Bytes::new(),
-                MetadataMap::new(),
+                ErrorDetails::with_bad_request(violations("", &validation_errors)),
             ),
             AppError::Json(error) => Self::internal(error.to_string()),
             AppError::DB(error) => Self::internal(error.to_string()),
-            AppError::Other(error) => Self::internal(error.to_string()),
+            AppError::Other(error) => match error.downcast::<auth::error::AuthError>() {
+                Ok(error) => error.into(),
+                Err(error) => Self::internal(error.to_string()),
+            },
             AppError::NotFound => Self::not_found("entity not found"),
-            AppError::Auth(error) => Self::internal(error.to_string()),
+            AppError::Auth(error) => error.into(),
+            AppError::Users(error) => error.into(),
         }
     }
 }
 
+// One google.rpc.BadRequest violation per error, sorted by field. Nested fields are joined with
+// "." and list items get their index, a field error can point at an item itself with an "index"
+// param.
+fn violations(prefix: &str, errors: &ValidationErrors) -> Vec<FieldViolation> {
+    let mut fields = errors.errors().iter().collect::<Vec<_>>();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+
+    fields
+        .into_iter()
+        .flat_map(|(field, kind)| {
+            let field = match prefix {
+                "" => field.to_string(),
+                prefix => format!("{prefix}.{field}"),
+            };
+
+            match kind {
+                ValidationErrorsKind::Field(errors) => errors
+                    .iter()
+                    .map(|error| {
+                        let field = match error.params.get("index") {
+                            Some(index) => format!("{field}[{index}]"),
+                            None => field.clone(),
+                        };
+
+                        FieldViolation::new(field, error.message.as_deref().unwrap_or(&error.code))
+                    })
+                    .collect(),
+                ValidationErrorsKind::Struct(errors) => violations(&field, errors),
+                ValidationErrorsKind::List(items) => items
+                    .iter()
+                    .flat_map(|(index, errors)| violations(&format!("{field}[{index}]"), errors))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("entity not found")]
@@ -33,7 +74,48 @@ pub enum AppError {
     #[error(transparent)]
     Auth(#[from] auth::error::AuthError),
     #[error(transparent)]
+    Users(#[from] users::error::UsersError),
+    #[error(transparent)]
     DB(#[from] sea_orm::DbErr),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use tonic::Status;
+    use tonic_types::StatusExt as _;
+    use validator::{ValidationError, ValidationErrors};
+
+    use super::AppError;
+
+    #[test]
+    fn should_report_each_violation() {
+        let mut errors = ValidationErrors::new();
+        errors.add("handle", ValidationError::new("reserved"));
+        for index in [1, 3] {
+            let mut error = ValidationError::new("uuid");
+            error.add_param("index".into(), &index);
+            errors.add("user_ids", error);
+        }
+
+        let status = Status::from(AppError::from(errors));
+        let violations = status
+            .get_details_bad_request()
+            .unwrap()
+            .field_violations
+            .into_iter()
+            .map(|it| (it.field, it.description))
+            .collect::<Vec<_>>();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(
+            violations,
+            [
+                ("handle".into(), "reserved".into()),
+                ("user_ids[1]".into(), "uuid".into()),
+                ("user_ids[3]".into(), "uuid".into()),
+            ]
+        );
+    }
+}
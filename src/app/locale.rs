@@ -0,0 +1,152 @@
+use chrono_tz::Tz;
+use icu_locale_core::Locale;
+use serde::Deserialize;
+use tonic::metadata::MetadataMap;
+use validator::{ValidationError, ValidationErrors};
+
+use super::error::AppError;
+
+#[derive(Deserialize, Clone)]
+pub struct LocaleSettings {
+    // Used when nothing in accept-language is supported.
+    pub default: String,
+    pub supported: Vec<String>,
+}
+
+// BCP 47 casing and separators, e.g. "EN_us" becomes "en-US". Extensions such as "-u-ca-..."
+// are dropped, only the language identifier is kept.
+pub fn canonicalize(tag: &str) -> Option<String> {
+    Locale::try_from_str(&tag.trim().replace('_', "-"))
+        .ok()
+        .map(|locale| locale.id.to_string())
+}
+
+// RFC 4647 lookup: subtags are removed from the end until a supported locale matches, so
+// "de-AT" resolves to "de" when only "de" is supported.
+pub fn resolve(settings: &LocaleSettings, tag: &str) -> Option<String> {
+    let mut tag = canonicalize(tag)?;
+
+    loop {
+        if let Some(supported) = settings
+            .supported
+            .iter()
+            .filter_map(|it| canonicalize(it))
+            .find(|it| it.eq_ignore_ascii_case(&tag))
+        {
+            return Some(supported);
+        }
+
+        tag.truncate(tag.rfind('-')?);
+    }
+}
+
+// Ranges are tried by descending quality, ties keep the order they were sent in.
+pub fn negotiate(settings: &LocaleSettings, accept_language: Option<&str>) -> String {
+    let mut ranges = accept_language
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|it| it.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |it| it.trim().parse::<f32>().ok())?;
+
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect::<Vec<_>>();
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| resolve(settings, tag))
+        .unwrap_or_else(|| settings.default.clone())
+}
+
+// An explicit locale has to be supported, without one the accept-language metadata is used.
+pub fn from_request(
+    settings: &LocaleSettings,
+    locale: Option<&str>,
+    metadata: &MetadataMap,
+) -> Result<String, AppError> {
+    match locale.filter(|it| !it.trim().is_empty()) {
+        Some(locale) => resolve(settings, locale).ok_or_else(|| {
+            let mut errors = ValidationErrors::new();
+            errors.add("locale", ValidationError::new("unsupported"));
+            errors.into()
+        }),
+        None => Ok(negotiate(
+            settings,
+            metadata
+                .get("accept-language")
+                .and_then(|it| it.to_str().ok()),
+        )),
+    }
+}
+
+// IANA name as spelled in the tz database, matched case-insensitively.
+pub fn timezone(value: &str) -> Option<&'static str> {
+    Tz::from_str_insensitive(value.trim())
+        .ok()
+        .map(|tz| tz.name())
+}
+
+pub fn validate_timezone(value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() || timezone(value).is_some() {
+        return Ok(());
+    }
+
+    Err(ValidationError::new("timezone"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonicalize, negotiate, resolve, timezone, LocaleSettings};
+
+    fn settings() -> LocaleSettings {
+        LocaleSettings {
+            default: "en".into(),
+            supported: vec!["en".into(), "en-GB".into(), "de".into(), "zh-Hant".into()],
+        }
+    }
+
+    #[test]
+    fn should_canonicalize_tags() {
+        assert_eq!(canonicalize("EN_us").as_deref(), Some("en-US"));
+        assert_eq!(canonicalize("zh-hant-tw").as_deref(), Some("zh-Hant-TW"));
+        assert_eq!(canonicalize("de-DE-u-co-phonebk").as_deref(), Some("de-DE"));
+        assert_eq!(canonicalize("not a locale"), None);
+        assert_eq!(canonicalize(""), None);
+    }
+
+    #[test]
+    fn should_resolve_supported_locales() {
+        assert_eq!(resolve(&settings(), "en-gb").as_deref(), Some("en-GB"));
+        assert_eq!(resolve(&settings(), "de-AT").as_deref(), Some("de"));
+        assert_eq!(
+            resolve(&settings(), "zh-Hant-TW").as_deref(),
+            Some("zh-Hant")
+        );
+        assert_eq!(resolve(&settings(), "zh-Hans"), None);
+        assert_eq!(resolve(&settings(), "fr"), None);
+    }
+
+    #[test]
+    fn should_negotiate_accept_language() {
+        assert_eq!(
+            negotiate(&settings(), Some("fr-CH, de;q=0.7, en;q=0.9")),
+            "en"
+        );
+        assert_eq!(negotiate(&settings(), Some("fr, de-CH;q=0.5")), "de");
+        assert_eq!(negotiate(&settings(), Some("de;q=0, *")), "en");
+        assert_eq!(negotiate(&settings(), Some("garbage;q=x")), "en");
+        assert_eq!(negotiate(&settings(), None), "en");
+    }
+
+    #[test]
+    fn should_canonicalize_timezones() {
+        assert_eq!(timezone("europe/berlin"), Some("Europe/Berlin"));
+        assert_eq!(timezone("UTC"), Some("UTC"));
+        assert_eq!(timezone("Mars/Olympus"), None);
+    }
+}
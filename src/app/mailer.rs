@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use log::info;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct MailerSettings {
+    pub from: String,
+}
+
+pub struct Message {
+    pub to: String,
+    pub subject: String,
+    #[expect(
+        dead_code,
+        reason = "only LogMailer exists so far, and it must not log the body"
+    )]
+    pub body: String,
+}
+
+#[tonic::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: Message) -> Result<(), Error>;
+}
+
+pub type SharedMailer = Arc<dyn Mailer>;
+
+// Logs that a message would have been sent instead of delivering it. Used until a real
+// transport is configured and in local development. The body is never logged, it can carry
+// confirmation links that act as credentials.
+pub struct LogMailer {
+    from: String,
+}
+
+impl LogMailer {
+    pub fn new(settings: &MailerSettings) -> Self {
+        Self {
+            from: settings.from.clone(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: Message) -> Result<(), Error> {
+        info!(
+            "mailer: from={} to={} subject={:?}",
+            self.from, message.to, message.subject
+        );
+
+        Ok(())
+    }
+}
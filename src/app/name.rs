@@ -0,0 +1,114 @@
+use unicode_segmentation::UnicodeSegmentation as _;
+
+// Primary language subtags where the family name is written before the given name.
+const FAMILY_NAME_FIRST: &[&str] = &["hu", "ja", "ko", "vi", "zh"];
+
+pub fn display_name(
+    first_name: &str,
+    last_name: &str,
+    handle: Option<&str>,
+    locale: Option<&str>,
+) -> String {
+    match parts(first_name, last_name, locale) {
+        ["", ""] => handle.map(|it| format!("@{it}")).unwrap_or_default(),
+        [part, ""] | ["", part] => part.into(),
+        [a, b] if is_cjk(a.chars().last()) && is_cjk(b.chars().next()) => format!("{a}{b}"),
+        [a, b] => format!("{a} {b}"),
+    }
+}
+
+// Names written without a space between their parts get a single initial, two ideographs would
+// read as a different name.
+pub fn initials(
+    first_name: &str,
+    last_name: &str,
+    handle: Option<&str>,
+    locale: Option<&str>,
+) -> String {
+    match parts(first_name, last_name, locale) {
+        ["", ""] => handle.map(initial).unwrap_or_default(),
+        [a, b] if is_cjk(a.chars().last()) && is_cjk(b.chars().next()) => initial(a),
+        parts => parts.into_iter().map(initial).collect(),
+    }
+}
+
+// Trimmed and in reading order, either may be empty.
+fn parts<'a>(first_name: &'a str, last_name: &'a str, locale: Option<&str>) -> [&'a str; 2] {
+    let (a, b) = if is_family_name_first(locale) {
+        (last_name, first_name)
+    } else {
+        (first_name, last_name)
+    };
+
+    [a.trim(), b.trim()]
+}
+
+fn is_family_name_first(locale: Option<&str>) -> bool {
+    locale
+        .and_then(|it| it.split(['-', '_']).next())
+        .is_some_and(|language| {
+            FAMILY_NAME_FIRST
+                .iter()
+                .any(|it| it.eq_ignore_ascii_case(language))
+        })
+}
+
+fn is_cjk(c: Option<char>) -> bool {
+    matches!(
+        c,
+        Some(
+            '\u{3040}'..='\u{30ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{ac00}'..='\u{d7af}',
+        )
+    )
+}
+
+// First grapheme cluster, so combining marks and emoji sequences stay intact. Leading
+// punctuation such as the quote in "'Ohana" is skipped when there is a letter to use instead.
+fn initial(part: &str) -> String {
+    let mut graphemes = part.graphemes(true);
+
+    graphemes
+        .clone()
+        .find(|it| it.chars().next().is_some_and(char::is_alphanumeric))
+        .or_else(|| graphemes.next())
+        .map(str::to_uppercase)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{display_name, initials};
+
+    #[test]
+    fn should_order_display_names() {
+        assert_eq!(display_name("Zoe", "Kim", None, None), "Zoe Kim");
+        assert_eq!(display_name("Zoe", "Kim", None, Some("ko-KR")), "Kim Zoe");
+        assert_eq!(display_name("太郎", "山田", None, Some("ja")), "山田太郎");
+        assert_eq!(
+            display_name("Taro", "Yamada", None, Some("ja")),
+            "Yamada Taro"
+        );
+        assert_eq!(display_name(" Zoe ", "", None, None), "Zoe");
+        assert_eq!(display_name("", "", Some("zoe"), None), "@zoe");
+        assert_eq!(display_name("", "Kim", None, None), "Kim");
+        assert_eq!(display_name("", "", None, None), "");
+    }
+
+    #[test]
+    fn should_take_initials() {
+        assert_eq!(initials("zoe", "kim", None, None), "ZK");
+        assert_eq!(initials("Zoe", "Kim", None, Some("hu")), "KZ");
+        assert_eq!(initials("e\u{0301}mile", "", None, None), "E\u{0301}");
+        assert_eq!(
+            initials("\u{1f469}\u{200d}\u{1f4bb}", "Kim", None, None),
+            "\u{1f469}\u{200d}\u{1f4bb}K"
+        );
+        assert_eq!(initials("'Ohana", "Kim", None, None), "OK");
+        assert_eq!(initials("太郎", "山田", None, Some("ja")), "山");
+        assert_eq!(initials("", "", Some("zoe"), None), "Z");
+        assert_eq!(initials("", "", None, None), "");
+    }
+}
@@ -3,7 +3,10 @@ use std::env;
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 
-use super::auth::settings::AuthSettings;
+use super::{
+    admin::settings::AdminSettings, auth::settings::AuthSettings, locale::LocaleSettings,
+    mailer::MailerSettings, storage::StorageSettings, users::settings::UsersSettings,
+};
 
 #[derive(Deserialize, Clone)]
 pub struct AppSettings {
@@ -11,6 +14,11 @@ pub struct AppSettings {
     pub http: HttpSettings,
     pub db: DBSettings,
     pub auth: AuthSettings,
+    pub users: UsersSettings,
+    pub admin: AdminSettings,
+    pub mailer: MailerSettings,
+    pub storage: StorageSettings,
+    pub locale: LocaleSettings,
 }
 
 #[derive(Deserialize, Clone)]
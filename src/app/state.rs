@@ -4,13 +4,25 @@ use anyhow::Error;
 use sea_orm::{ConnectOptions, Database, DbConn};
 use tokio::fs;
 
-use super::settings::AppSettings;
+use super::{
+    auth::dpop::ReplayCache,
+    mailer::{LogMailer, SharedMailer},
+    settings::AppSettings,
+    storage::{self, SharedStorage},
+    users::watch::Hub,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub settings: AppSettings,
     pub db: Arc<DbConn>,
     pub private_key: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub secret_key: Vec<u8>,
+    pub dpop_replay_cache: ReplayCache,
+    pub mailer: SharedMailer,
+    pub storage: SharedStorage,
+    pub users_hub: Hub,
 }
 
 impl AppState {
@@ -24,10 +36,26 @@ impl AppState {
             .await?
             .into_bytes();
 
+        let public_key = fs::read_to_string(&settings.auth.public_key_file)
+            .await?
+            .into_bytes();
+
+        let secret_key = fs::read(&settings.auth.secret_key_file).await?;
+
+        let mailer = Arc::new(LogMailer::new(&settings.mailer));
+        let storage = storage::storage(&settings.storage)?;
+        let users_hub = Hub::new(settings.users.watch.capacity);
+
         Ok(Self {
             settings,
             db,
             private_key,
+            public_key,
+            secret_key,
+            dpop_replay_cache: ReplayCache::default(),
+            mailer,
+            storage,
+            users_hub,
         })
     }
 }
@@ -39,8 +67,25 @@ mod tests {
     use sea_orm::DatabaseConnection;
 
     use crate::app::{
-        auth::settings::{AuthSettings, RPSettings},
+        admin::settings::{AdminSettings, AuditEventsSettings},
+        auth::{
+            dpop::ReplayCache,
+            settings::{
+                AuthSettings, DataExportSettings, DeletionSettings, DeviceSettings, DpopSettings,
+                EmailChangeSettings, RPSettings, ReauthenticationSettings, ServiceTokenSettings,
+            },
+        },
+        locale::LocaleSettings,
+        mailer::{LogMailer, MailerSettings},
         settings::{AppSettings, DBSettings, HttpSettings},
+        storage::{Backend, LocalStorage, StorageSettings},
+        users::{
+            settings::{
+                AvatarSettings, GetUsersSettings, HandleSettings, ListSettings,
+                PreferencesSettings, SearchSettings, UsersSettings, WatchSettings,
+            },
+            watch::Hub,
+        },
     };
 
     use super::AppState;
@@ -62,10 +107,85 @@ mod tests {
                             name: String::default(),
                         },
                         private_key_file: String::default(),
+                        public_key_file: String::default(),
+                        secret_key_file: String::default(),
+                        service_token: ServiceTokenSettings { ttl: 0 },
+                        dpop: DpopSettings {
+                            origin: String::default(),
+                            max_age: 0,
+                            nonce_ttl: 1,
+                        },
+                        reauthentication: ReauthenticationSettings {
+                            max_age: 0,
+                            token_ttl: 0,
+                        },
+                        device: DeviceSettings {
+                            verification_uri: String::default(),
+                            ttl: 0,
+                            interval: 0,
+                        },
+                        email_change: EmailChangeSettings {
+                            confirmation_uri: String::default(),
+                            ttl: 0,
+                        },
+                        deletion: DeletionSettings {
+                            grace_period: 0,
+                            purge_interval: 1,
+                        },
+                        data_export: DataExportSettings { ttl: 0, timeout: 0 },
+                    },
+                    users: UsersSettings {
+                        handle: HandleSettings { change_cooldown: 0 },
+                        avatar: AvatarSettings {
+                            max_age: 0,
+                            max_upload_size: 0,
+                        },
+                        preferences: PreferencesSettings { max_size: 0 },
+                        get_users: GetUsersSettings { max_batch_size: 0 },
+                        watch: WatchSettings {
+                            max_user_ids: 0,
+                            capacity: 1,
+                        },
+                        search: SearchSettings {
+                            default_page_size: 0,
+                            max_page_size: 0,
+                        },
+                        list: ListSettings {
+                            default_page_size: 0,
+                            max_page_size: 0,
+                            export_batch_size: 1,
+                        },
+                    },
+                    admin: AdminSettings {
+                        audit_events: AuditEventsSettings {
+                            default_page_size: 0,
+                            max_page_size: 0,
+                        },
+                    },
+                    mailer: MailerSettings {
+                        from: String::default(),
+                    },
+                    storage: StorageSettings {
+                        public_url: String::default(),
+                        backend: Backend::Local {
+                            dir: String::default(),
+                        },
+                    },
+                    locale: LocaleSettings {
+                        default: String::default(),
+                        supported: vec![],
                     },
                 },
                 db: Arc::new(DatabaseConnection::default()),
                 private_key: vec![],
+                public_key: vec![],
+                secret_key: vec![],
+                dpop_replay_cache: ReplayCache::default(),
+                mailer: Arc::new(LogMailer::new(&MailerSettings {
+                    from: String::default(),
+                })),
+                storage: Arc::new(LocalStorage::new("", "")),
+                users_hub: Hub::new(1),
             }
         }
     }
@@ -0,0 +1,194 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Error;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse as _, Response},
+    routing::get,
+    Router,
+};
+use log::error;
+use serde::Deserialize;
+use tokio::fs;
+
+use super::state::AppState;
+
+#[cfg(feature = "s3")]
+mod s3;
+
+#[derive(Deserialize, Clone)]
+pub struct StorageSettings {
+    // Prefix for object URLs handed to clients, either this service's /api/media route or a
+    // CDN in front of the bucket.
+    pub public_url: String,
+    pub backend: Backend,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Backend {
+    Local {
+        dir: String,
+    },
+    #[cfg(feature = "s3")]
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: String,
+        access_key: String,
+        secret_key: String,
+    },
+}
+
+#[tonic::async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, content_type: &str, body: Vec<u8>) -> Result<(), Error>;
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+    fn url(&self, key: &str) -> String;
+}
+
+pub type SharedStorage = Arc<dyn Storage>;
+
+pub fn storage(settings: &StorageSettings) -> Result<SharedStorage, Error> {
+    Ok(match &settings.backend {
+        Backend::Local { dir } => Arc::new(LocalStorage::new(dir, &settings.public_url)),
+        #[cfg(feature = "s3")]
+        Backend::S3 {
+            bucket,
+            region,
+            endpoint,
+            access_key,
+            secret_key,
+        } => Arc::new(s3::S3Storage::new(
+            bucket,
+            region,
+            endpoint,
+            access_key,
+            secret_key,
+            &settings.public_url,
+        )?),
+    })
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/media/{*key}", get(get_object))
+}
+
+// Keys are never reused, a new upload always gets a new key.
+async fn get_object(
+    State(AppState { storage, .. }): State<AppState>,
+    Path(key): Path<String>,
+) -> Result<Response, StatusCode> {
+    let body = storage
+        .get(&key)
+        .await
+        .map_err(|err| {
+            error!("storage: get {} failed: {}", key, err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let content_type = match key.rsplit_once('.').map(|(_, it)| it) {
+        Some("jpg") => "image/jpeg",
+        _ => "application/octet-stream",
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn url(public_url: &str, key: &str) -> String {
+    format!("{}/{}", public_url.trim_end_matches('/'), key)
+}
+
+pub struct LocalStorage {
+    dir: PathBuf,
+    public_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(dir: &str, public_url: &str) -> Self {
+        Self {
+            dir: dir.into(),
+            public_url: public_url.into(),
+        }
+    }
+
+    // Keys come from request paths, so anything that could leave `dir` is rejected.
+    fn path(&self, key: &str) -> Result<PathBuf, Error> {
+        let valid = key.split('/').all(|segment| {
+            !segment.is_empty()
+                && !segment.starts_with('.')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+
+        if !valid {
+            return Err(anyhow::anyhow!("invalid key {:?}", key));
+        }
+
+        Ok(self.dir.join(key))
+    }
+}
+
+#[tonic::async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, _content_type: &str, body: Vec<u8>) -> Result<(), Error> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(path, body).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let Ok(path) = self.path(key) else {
+            return Ok(None);
+        };
+
+        match fs::read(path).await {
+            Ok(body) => Ok(Some(body)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        url(&self.public_url, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LocalStorage;
+
+    #[test]
+    fn should_reject_keys_outside_dir() {
+        let storage = LocalStorage::new("/srv/media", "/api/media");
+
+        assert!(storage.path("pictures/a/b/256.jpg").is_ok());
+        assert!(storage.path("../etc/passwd").is_err());
+        assert!(storage.path("pictures/../../etc").is_err());
+        assert!(storage.path("/etc/passwd").is_err());
+        assert!(storage.path("pictures//256.jpg").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::Error;
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+
+use super::{url, Storage};
+
+// Works with any S3-compatible service, the bucket is addressed path-style.
+pub struct S3Storage {
+    bucket: Box<Bucket>,
+    public_url: String,
+}
+
+impl S3Storage {
+    pub fn new(
+        bucket: &str,
+        region: &str,
+        endpoint: &str,
+        access_key: &str,
+        secret_key: &str,
+        public_url: &str,
+    ) -> Result<Self, Error> {
+        let region = Region::Custom {
+            region: region.into(),
+            endpoint: endpoint.into(),
+        };
+        let credentials = Credentials::new(Some(access_key), Some(secret_key), None, None, None)?;
+
+        Ok(Self {
+            bucket: Bucket::new(bucket, region, credentials)?.with_path_style(),
+            public_url: public_url.into(),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, content_type: &str, body: Vec<u8>) -> Result<(), Error> {
+        self.bucket
+            .put_object_with_content_type(key, &body, content_type)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        match self.bucket.get_object(key).await {
+            Ok(response) => Ok(Some(response.bytes().to_vec())),
+            Err(S3Error::HttpFailWithBody(404, _)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.bucket.delete_object(key).await?;
+
+        Ok(())
+    }
+
+    fn url(&self, key: &str) -> String {
+        url(&self.public_url, key)
+    }
+}
@@ -1,12 +1,46 @@
+use std::time::Duration;
+
+use axum::Router;
 use flux_users_api::users_service_server::UsersServiceServer;
 use grpc::GrpcUsersService;
+use log::error;
+use tokio::{task::JoinHandle, time};
 
 use super::state::AppState;
 
+mod avatar;
+pub(super) mod cursor;
+pub(super) mod error;
 mod grpc;
-mod repo;
+mod handle;
+mod http;
+pub(super) mod picture;
+mod preferences;
+pub(super) mod repo;
 mod service;
+pub(super) mod settings;
+pub(super) mod watch;
 
 pub fn users_service(state: AppState) -> UsersServiceServer<GrpcUsersService> {
+    // Leaves room for the rest of the message around an upload of the maximum size.
+    let max_message_size = state.settings.users.avatar.max_upload_size + 1024;
+
     UsersServiceServer::new(GrpcUsersService::new(state))
+        .max_decoding_message_size(max_message_size)
+}
+
+pub fn watch_job(state: AppState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = watch::listen(&state.db, &state.users_hub).await {
+                error!("users: change listener failed: {}", err);
+            }
+
+            time::sleep(Duration::from_secs(1)).await;
+        }
+    })
+}
+
+pub fn router() -> Router<AppState> {
+    http::router()
 }
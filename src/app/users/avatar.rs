@@ -0,0 +1,133 @@
+use serde::Deserialize;
+
+use crate::app::color;
+
+// Part of every ETag, bump it when the rendering changes so caches drop old images.
+pub const VERSION: u32 = 1;
+
+pub const MIN_SIZE: u32 = 16;
+pub const MAX_SIZE: u32 = 512;
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Svg,
+    #[cfg(feature = "png")]
+    Png,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Svg => "image/svg+xml",
+            #[cfg(feature = "png")]
+            Self::Png => "image/png",
+        }
+    }
+}
+
+// A square on a 100x100 view box, clients apply their own rounding.
+pub fn svg(initials: &str, label: &str, color: &color::Color, theme: Theme, size: u32) -> String {
+    let fill = match theme {
+        Theme::Light => color.light,
+        Theme::Dark => color.dark,
+    };
+    let font_size = match initials.chars().count() {
+        0 | 1 => 48,
+        _ => 40,
+    };
+
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" "#,
+            r#"viewBox="0 0 100 100" role="img" aria-label="{label}">"#,
+            r#"<rect width="100" height="100" fill="{fill}"/>"#,
+            r#"<text x="50" y="50" dy="0.35em" text-anchor="middle" "#,
+            r#"font-family="system-ui, -apple-system, 'Segoe UI', Roboto, sans-serif" "#,
+            r##"font-size="{font_size}" font-weight="600" fill="#ffffff">{initials}</text>"##,
+            "</svg>"
+        ),
+        size = size,
+        label = escape(label),
+        fill = fill.hex(),
+        font_size = font_size,
+        initials = escape(initials),
+    )
+}
+
+#[cfg(feature = "png")]
+pub fn png(svg: &str, size: u32) -> Result<Vec<u8>, anyhow::Error> {
+    use std::sync::{Arc, LazyLock};
+
+    use resvg::{
+        tiny_skia::{Pixmap, Transform},
+        usvg::{fontdb, Options, Tree},
+    };
+
+    // Loading system fonts takes a while, so it happens once per process.
+    static FONTDB: LazyLock<Arc<fontdb::Database>> = LazyLock::new(|| {
+        let mut fontdb = fontdb::Database::new();
+        fontdb.load_system_fonts();
+        Arc::new(fontdb)
+    });
+
+    let options = Options {
+        fontdb: FONTDB.clone(),
+        ..Default::default()
+    };
+    let tree = Tree::from_str(svg, &options)?;
+
+    let mut pixmap = Pixmap::new(size, size).ok_or(anyhow::anyhow!("invalid size {}", size))?;
+    let scale = size as f32 / tree.size().width();
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    Ok(pixmap.encode_png()?)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::color::PALETTE;
+
+    use super::{svg, Theme};
+
+    #[test]
+    fn should_render_escaped_svg() {
+        let color = &PALETTE[0];
+        let svg = svg("Z<", "Zoe \"K\"", color, Theme::Dark, 64);
+
+        assert!(svg.contains(r#"width="64""#));
+        assert!(svg.contains(&format!(r#"fill="{}""#, color.dark.hex())));
+        assert!(svg.contains(">Z&lt;</text>"));
+        assert!(svg.contains(r#"aria-label="Zoe &quot;K&quot;""#));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn should_render_png() {
+        let png = super::png(&svg("ZK", "Zoe Kim", &PALETTE[0], Theme::Light, 32), 32).unwrap();
+
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
@@ -0,0 +1,114 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{de::DeserializeOwned, Serialize};
+use validator::{ValidationError, ValidationErrors};
+
+use crate::app::error::AppError;
+
+// Page tokens are opaque to clients. They are not signed, a forged one only moves the position
+// within results the caller may see anyway.
+pub fn encode<T: Serialize>(cursor: &T) -> Result<String, AppError> {
+    Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor)?))
+}
+
+pub fn decode<T: DeserializeOwned>(token: &str) -> Result<T, AppError> {
+    URL_SAFE_NO_PAD
+        .decode(token)
+        .ok()
+        .and_then(|it| serde_json::from_slice(&it).ok())
+        .ok_or_else(|| invalid().into())
+}
+
+// Zero asks for the default.
+pub fn page_size(requested: u64, default: u64, max: u64) -> u64 {
+    match requested {
+        0 => default,
+        requested => requested.min(max),
+    }
+}
+
+// Pages are read with one extra row to tell whether another page follows. It is dropped here and
+// the token points after the last row that is kept.
+pub fn next_page<T, C: Serialize>(
+    rows: &mut Vec<T>,
+    page_size: u64,
+    cursor: impl FnOnce(&T) -> C,
+) -> Result<Option<String>, AppError> {
+    if rows.len() as u64 <= page_size {
+        return Ok(None);
+    }
+
+    rows.truncate(page_size as usize);
+    rows.last().map(|it| encode(&cursor(it))).transpose()
+}
+
+pub fn invalid() -> ValidationErrors {
+    let mut errors = ValidationErrors::new();
+    errors.add("page_token", ValidationError::new("invalid"));
+    errors
+}
+
+// Tokens are checked before anything is read. The list call gets a connection that is never
+// opened, so it fails loudly if a foreign token makes it as far as the database.
+#[cfg(test)]
+pub async fn assert_rejects_token<C, T, F, Fut>(cursor: &C, list: F)
+where
+    C: Serialize,
+    F: FnOnce(sea_orm::DbConn, String) -> Fut,
+    Fut: std::future::Future<Output = Result<T, AppError>>,
+{
+    let result = list(sea_orm::DbConn::default(), encode(cursor).unwrap()).await;
+
+    assert!(matches!(
+        result,
+        Err(AppError::Validation(it)) if it.field_errors().contains_key("page_token")
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::app::users::service::search_users::Cursor;
+
+    use super::{decode, encode, next_page, page_size};
+
+    #[test]
+    fn should_round_trip_cursors() {
+        let id = Uuid::now_v7();
+        let token = encode(&Cursor {
+            query: "zoe".into(),
+            email: false,
+            rank: 0.428_571_43,
+            id,
+        })
+        .unwrap();
+
+        let cursor: Cursor = decode(&token).unwrap();
+        assert_eq!(cursor.query, "zoe");
+        assert!(!cursor.email);
+        assert_eq!(cursor.rank, 0.428_571_43);
+        assert_eq!(cursor.id, id);
+
+        assert!(decode::<Cursor>("not a token").is_err());
+        assert!(decode::<Cursor>(&encode(&"other").unwrap()).is_err());
+    }
+
+    #[test]
+    fn should_cap_page_size() {
+        assert_eq!(page_size(0, 20, 100), 20);
+        assert_eq!(page_size(5, 20, 100), 5);
+        assert_eq!(page_size(1000, 20, 100), 100);
+    }
+
+    #[test]
+    fn should_point_next_page_after_last_row() {
+        let mut rows = vec![1, 2, 3];
+        let token = next_page(&mut rows, 2, |it| *it).unwrap();
+        assert_eq!(rows, [1, 2]);
+        assert_eq!(decode::<i32>(&token.unwrap()).unwrap(), 2);
+
+        let mut rows = vec![1, 2];
+        assert!(next_page(&mut rows, 2, |it| *it).unwrap().is_none());
+        assert_eq!(rows, [1, 2]);
+    }
+}
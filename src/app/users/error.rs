@@ -0,0 +1,45 @@
+use chrono::NaiveDateTime;
+use thiserror::Error;
+use tonic::Status;
+
+#[derive(Error, Debug)]
+pub enum UsersError {
+    #[error("HANDLE_UNAVAILABLE")]
+    HandleUnavailable,
+    #[error("HANDLE_ALREADY_CLAIMED")]
+    HandleAlreadyClaimed,
+    #[error("HANDLE_NOT_CLAIMED")]
+    HandleNotClaimed,
+    #[error("HANDLE_CHANGE_COOLDOWN")]
+    CooldownActive(NaiveDateTime),
+    #[error("PREFERENCES_VERSION_MISMATCH")]
+    PreferencesVersionMismatch(i64),
+}
+
+impl From<UsersError> for Status {
+    fn from(error: UsersError) -> Self {
+        match error {
+            UsersError::HandleUnavailable => Self::already_exists(error.to_string()),
+            UsersError::HandleAlreadyClaimed | UsersError::HandleNotClaimed => {
+                Self::failed_precondition(error.to_string())
+            }
+            UsersError::CooldownActive(until) => {
+                let mut status = Self::failed_precondition(error.to_string());
+                if let Ok(until) = until.and_utc().to_rfc3339().parse() {
+                    status.metadata_mut().insert("retry-at", until);
+                }
+
+                status
+            }
+            // Clients re-read, reapply their change and retry with the current version.
+            UsersError::PreferencesVersionMismatch(version) => {
+                let mut status = Self::aborted(error.to_string());
+                if let Ok(version) = version.to_string().parse() {
+                    status.metadata_mut().insert("current-version", version);
+                }
+
+                status
+            }
+        }
+    }
+}
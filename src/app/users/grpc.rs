@@ -1,13 +1,29 @@
-use anyhow::Error;
+use std::pin::Pin;
+
+use chrono::Utc;
 use flux_users_api::{
-    get_users_response::User, users_service_server::UsersService, GetUsersRequest, GetUsersResponse,
+    avatar_color::Shade, get_users_response::User, users_service_server::UsersService, AvatarColor,
+    ChangeHandleRequest, ChangeHandleResponse, CheckHandleAvailabilityRequest,
+    CheckHandleAvailabilityResponse, ClaimHandleRequest, ClaimHandleResponse, DeleteAvatarRequest,
+    DeleteAvatarResponse, GetPreferencesRequest, GetPreferencesResponse, GetUserByHandleRequest,
+    GetUserByHandleResponse, GetUsersRequest, GetUsersResponse, ListAvatarColorsRequest,
+    ListAvatarColorsResponse, SearchUsersRequest, SearchUsersResponse, UpdatePreferencesRequest,
+    UpdatePreferencesResponse, UpdateProfileRequest, UpdateProfileResponse, UploadAvatarRequest,
+    UploadAvatarResponse, WatchUsersRequest, WatchUsersResponse,
 };
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt as _};
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
 
-use crate::app::{error::AppError, state::AppState};
+use crate::app::{
+    auth::principal::{self, permission, scope},
+    color,
+    error::AppError,
+    state::AppState,
+};
 
-use super::service;
+use super::{handle, repo, service};
 
 pub struct GrpcUsersService {
     pub state: AppState,
@@ -19,24 +35,192 @@ impl GrpcUsersService {
     }
 }
 
+type WatchUsersStream = Pin<Box<dyn Stream<Item = Result<WatchUsersResponse, Status>> + Send>>;
+
 #[tonic::async_trait]
 impl UsersService for GrpcUsersService {
+    type WatchUsersStream = WatchUsersStream;
+
     async fn get_users(
         &self,
         request: Request<GetUsersRequest>,
     ) -> Result<Response<GetUsersResponse>, Status> {
+        principal::authenticate(&self.state, &request)
+            .await?
+            .require_scope(scope::USERS_READ)?;
+
         let response = get_users(&self.state, request.into_inner()).await?;
 
         Ok(Response::new(response))
     }
+
+    async fn search_users(
+        &self,
+        request: Request<SearchUsersRequest>,
+    ) -> Result<Response<SearchUsersResponse>, Status> {
+        let principal = principal::authenticate(&self.state, &request).await?;
+        principal.require_scope(scope::USERS_READ)?;
+        let email = principal
+            .has_permission(&self.state.db, permission::USERS_EMAIL_SEARCH)
+            .await?;
+
+        let response = search_users(&self.state, email, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn watch_users(
+        &self,
+        request: Request<WatchUsersRequest>,
+    ) -> Result<Response<WatchUsersStream>, Status> {
+        principal::authenticate(&self.state, &request)
+            .await?
+            .require_scope(scope::USERS_READ)?;
+
+        let stream = watch_users(&self.state, request.into_inner())?;
+
+        Ok(Response::new(stream))
+    }
+
+    async fn update_profile(
+        &self,
+        request: Request<UpdateProfileRequest>,
+    ) -> Result<Response<UpdateProfileResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = update_profile(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn check_handle_availability(
+        &self,
+        request: Request<CheckHandleAvailabilityRequest>,
+    ) -> Result<Response<CheckHandleAvailabilityResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response =
+            check_handle_availability(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn claim_handle(
+        &self,
+        request: Request<ClaimHandleRequest>,
+    ) -> Result<Response<ClaimHandleResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = claim_handle(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn change_handle(
+        &self,
+        request: Request<ChangeHandleRequest>,
+    ) -> Result<Response<ChangeHandleResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = change_handle(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn get_user_by_handle(
+        &self,
+        request: Request<GetUserByHandleRequest>,
+    ) -> Result<Response<GetUserByHandleResponse>, Status> {
+        principal::authenticate(&self.state, &request)
+            .await?
+            .require_scope(scope::USERS_READ)?;
+
+        let response = get_user_by_handle(&self.state, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_avatar_colors(
+        &self,
+        request: Request<ListAvatarColorsRequest>,
+    ) -> Result<Response<ListAvatarColorsResponse>, Status> {
+        principal::authenticate(&self.state, &request)
+            .await?
+            .require_scope(scope::USERS_READ)?;
+
+        Ok(Response::new(ListAvatarColorsResponse {
+            avatar_colors: color::PALETTE.iter().map(AvatarColor::from).collect(),
+        }))
+    }
+
+    async fn upload_avatar(
+        &self,
+        request: Request<UploadAvatarRequest>,
+    ) -> Result<Response<UploadAvatarResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = upload_avatar(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn delete_avatar(
+        &self,
+        request: Request<DeleteAvatarRequest>,
+    ) -> Result<Response<DeleteAvatarResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = service::delete_avatar(&self.state.db, &self.state.storage, user_id).await?;
+
+        Ok(Response::new(DeleteAvatarResponse {
+            user: Some(User::from(&response.user)),
+        }))
+    }
+
+    async fn get_preferences(
+        &self,
+        request: Request<GetPreferencesRequest>,
+    ) -> Result<Response<GetPreferencesResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = service::get_preferences(&self.state.db, user_id).await?;
+
+        Ok(Response::new(response.into()))
+    }
+
+    async fn update_preferences(
+        &self,
+        request: Request<UpdatePreferencesRequest>,
+    ) -> Result<Response<UpdatePreferencesResponse>, Status> {
+        let user_id = principal::authenticate(&self.state, &request)
+            .await?
+            .require_session()?;
+
+        let response = update_preferences(&self.state, user_id, request.into_inner()).await?;
+
+        Ok(Response::new(response))
+    }
 }
 
 async fn get_users(
-    state: &AppState,
+    AppState { db, settings, .. }: &AppState,
     request: GetUsersRequest,
 ) -> Result<GetUsersResponse, AppError> {
-    let AppState { db, .. } = state;
-    let response = service::get_users(&db, request.try_into()?).await?;
+    let response = service::get_users(db, &settings.users, request.try_into()?).await?;
 
     Ok(response.into())
 }
@@ -45,34 +229,563 @@ impl TryFrom<GetUsersRequest> for service::GetUsersRequest {
     type Error = AppError;
 
     fn try_from(request: GetUsersRequest) -> Result<Self, Self::Error> {
-        let data = Self {
-            user_ids: request
-                .user_ids
-                .iter()
-                .map(|user_id| -> Result<Uuid, Error> { Ok(Uuid::parse_str(user_id)?) })
-                .collect::<Result<Vec<Uuid>, Error>>()?,
-        };
+        Ok(Self {
+            user_ids: parse_user_ids(&request.user_ids)?,
+        })
+    }
+}
 
-        Ok(data)
+// Every malformed id is reported with its index, not just the first one.
+fn parse_user_ids(values: &[String]) -> Result<Vec<Uuid>, AppError> {
+    let mut errors = ValidationErrors::new();
+    let mut user_ids = Vec::with_capacity(values.len());
+
+    for (index, value) in values.iter().enumerate() {
+        match Uuid::parse_str(value) {
+            Ok(user_id) => user_ids.push(user_id),
+            Err(_) => {
+                let mut error = ValidationError::new("uuid");
+                error.add_param("index".into(), &index);
+                errors.add("user_ids", error);
+            }
+        }
     }
+
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    Ok(user_ids)
+}
+
+async fn search_users(
+    AppState { db, settings, .. }: &AppState,
+    email: bool,
+    request: SearchUsersRequest,
+) -> Result<SearchUsersResponse, AppError> {
+    let request = service::search_users::Request {
+        email,
+        ..request.try_into()?
+    };
+    let response = service::search_users(db, &settings.users, request).await?;
+
+    Ok(response.into())
+}
+
+mod search_users {
+    use flux_users_api::{get_users_response::User, SearchUsersRequest, SearchUsersResponse};
+    use validator::Validate as _;
+
+    use crate::app::{
+        error::AppError,
+        users::service::search_users::{normalize, Request, Response},
+    };
+
+    impl TryFrom<SearchUsersRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: SearchUsersRequest) -> Result<Self, Self::Error> {
+            let data = Self {
+                query: normalize(request.query()),
+                page_size: request.page_size(),
+                page_token: request.page_token,
+                email: false,
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    impl From<Response> for SearchUsersResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                users: res.users.iter().map(User::from).collect(),
+                next_page_token: res.next_page_token,
+            }
+        }
+    }
+}
+
+fn watch_users(
+    AppState {
+        db,
+        settings,
+        users_hub,
+        ..
+    }: &AppState,
+    request: WatchUsersRequest,
+) -> Result<WatchUsersStream, AppError> {
+    let request = service::GetUsersRequest {
+        user_ids: parse_user_ids(&request.user_ids)?,
+    };
+    let receiver = service::watch_users(db, users_hub, &settings.users, request)?;
+
+    Ok(Box::pin(ReceiverStream::new(receiver).map(|user| {
+        Ok(WatchUsersResponse {
+            user: Some(User::from(&user?)),
+        })
+    })))
 }
 
 impl Into<GetUsersResponse> for service::GetUsersResponse {
     fn into(self) -> GetUsersResponse {
         GetUsersResponse {
-            users: self
-                .users
-                .iter()
-                .map(|user| User {
-                    user_id: Some(user.id.into()),
-                    first_name: Some(user.first_name.clone()),
-                    last_name: Some(user.last_name.clone()),
-                    locale: user.locale.clone(),
-                    name: Some(user.name()),
-                    abbr: Some(user.abbr()),
-                    color: Some(user.color()),
-                })
-                .collect(),
+            users: self.users.iter().map(User::from).collect(),
+            missing_user_ids: self.missing_user_ids.iter().map(Uuid::to_string).collect(),
+        }
+    }
+}
+
+impl From<&repo::user::Model> for User {
+    fn from(user: &repo::user::Model) -> Self {
+        // Deleted users keep rendering in old conversations, without any of their profile.
+        if user.is_deleted() {
+            return Self {
+                user_id: Some(user.id.into()),
+                name: Some(repo::user::DELETED_USER_NAME.into()),
+                abbr: Some(repo::user::DELETED_USER_ABBR.into()),
+                color: Some(user.avatar_color().light.hsla()),
+                avatar_color: Some(user.avatar_color().into()),
+                deleted: Some(true),
+                status: Some(status(user)),
+                ..Default::default()
+            };
         }
+
+        Self {
+            user_id: Some(user.id.into()),
+            first_name: Some(user.first_name.clone()),
+            last_name: Some(user.last_name.clone()),
+            locale: user.locale.clone(),
+            name: Some(user.name()),
+            abbr: Some(user.abbr()),
+            color: Some(user.avatar_color().light.hsla()),
+            avatar_color: Some(user.avatar_color().into()),
+            deleted: Some(false),
+            status: Some(status(user)),
+            handle: user.handle.clone(),
+            avatar_url: user.picture_url.clone(),
+            timezone: user.timezone.clone(),
+        }
+    }
+}
+
+impl From<&color::Color> for AvatarColor {
+    fn from(color: &color::Color) -> Self {
+        let shade = |rgb: color::Rgb| Shade {
+            hex: Some(rgb.hex()),
+            hsla: Some(rgb.hsla()),
+        };
+
+        Self {
+            name: Some(color.name.into()),
+            light: Some(shade(color.light)),
+            dark: Some(shade(color.dark)),
+        }
+    }
+}
+
+fn status(user: &repo::user::Model) -> String {
+    match user.status_at(Utc::now().naive_utc()) {
+        repo::user::Status::Active => "active",
+        repo::user::Status::Suspended => "suspended",
+        repo::user::Status::Banned => "banned",
+    }
+    .into()
+}
+
+async fn update_profile(
+    AppState { db, settings, .. }: &AppState,
+    user_id: Uuid,
+    request: UpdateProfileRequest,
+) -> Result<UpdateProfileResponse, AppError> {
+    let request = service::update_profile::Request {
+        user_id,
+        ..request.try_into()?
+    };
+    let response = service::update_profile(db, &settings.locale, request).await?;
+
+    Ok(response.into())
+}
+
+mod update_profile {
+    use flux_users_api::{get_users_response::User, UpdateProfileRequest, UpdateProfileResponse};
+    use uuid::Uuid;
+    use validator::{Validate as _, ValidationError, ValidationErrors};
+
+    use crate::app::{
+        error::AppError,
+        users::service::update_profile::{normalize, Request, Response, FIELDS},
+    };
+
+    impl TryFrom<UpdateProfileRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: UpdateProfileRequest) -> Result<Self, Self::Error> {
+            let paths = request
+                .update_mask
+                .map(|mask| mask.paths)
+                .unwrap_or_default();
+
+            let mut errors = ValidationErrors::new();
+            for path in &paths {
+                if !FIELDS.contains(&path.as_str()) {
+                    errors.add("update_mask", ValidationError::new("unknown_field"));
+                }
+            }
+            if !errors.is_empty() {
+                return Err(errors.into());
+            }
+
+            // Without a mask every field present in the request is updated.
+            let masked =
+                |field: &'static str, value: Option<String>| -> Result<Option<String>, AppError> {
+                    if paths.is_empty() {
+                        return Ok(value.as_deref().map(normalize));
+                    }
+
+                    if !paths.iter().any(|path| path == field) {
+                        return Ok(None);
+                    }
+
+                    match value {
+                        Some(value) => Ok(Some(normalize(&value))),
+                        None => {
+                            let mut errors = ValidationErrors::new();
+                            errors.add(field, ValidationError::new("required"));
+                            Err(errors.into())
+                        }
+                    }
+                };
+
+            let data = Self {
+                user_id: Uuid::nil(),
+                first_name: masked("first_name", request.first_name)?,
+                last_name: masked("last_name", request.last_name)?,
+                locale: masked("locale", request.locale)?,
+                preferred_color: masked("preferred_color", request.preferred_color)?,
+                timezone: masked("timezone", request.timezone)?,
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+
+    impl From<Response> for UpdateProfileResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                user: Some(User::from(&res.user)),
+            }
+        }
+    }
+}
+
+async fn check_handle_availability(
+    AppState { db, .. }: &AppState,
+    user_id: Uuid,
+    request: CheckHandleAvailabilityRequest,
+) -> Result<CheckHandleAvailabilityResponse, AppError> {
+    let request = service::check_handle_availability::Request {
+        user_id,
+        handle: handle::normalize(request.handle()),
+    };
+    let response = service::check_handle_availability(db, request).await?;
+
+    Ok(response.into())
+}
+
+mod check_handle_availability {
+    use flux_users_api::CheckHandleAvailabilityResponse;
+
+    use crate::app::users::service::check_handle_availability::{Availability, Response};
+
+    impl From<Response> for CheckHandleAvailabilityResponse {
+        fn from(
+            Response {
+                handle,
+                availability,
+            }: Response,
+        ) -> Self {
+            let reason = match availability {
+                Availability::Available => None,
+                Availability::Invalid => Some("invalid"),
+                Availability::Reserved => Some("reserved"),
+                Availability::Taken => Some("taken"),
+            };
+
+            Self {
+                available: Some(reason.is_none()),
+                reason: reason.map(Into::into),
+                handle: Some(handle),
+            }
+        }
+    }
+}
+
+async fn claim_handle(
+    AppState { db, .. }: &AppState,
+    user_id: Uuid,
+    request: ClaimHandleRequest,
+) -> Result<ClaimHandleResponse, AppError> {
+    let response = service::claim_handle(db, (user_id, request.handle()).try_into()?).await?;
+
+    Ok(ClaimHandleResponse {
+        user: Some(User::from(&response.user)),
+    })
+}
+
+async fn change_handle(
+    AppState { db, settings, .. }: &AppState,
+    user_id: Uuid,
+    request: ChangeHandleRequest,
+) -> Result<ChangeHandleResponse, AppError> {
+    let response =
+        service::change_handle(db, &settings.users, (user_id, request.handle()).try_into()?)
+            .await?;
+
+    Ok(ChangeHandleResponse {
+        user: Some(User::from(&response.user)),
+    })
+}
+
+mod claim_handle {
+    use uuid::Uuid;
+    use validator::Validate as _;
+
+    use crate::app::{
+        error::AppError,
+        users::{handle::normalize, service::claim_handle::Request},
+    };
+
+    impl TryFrom<(Uuid, &str)> for Request {
+        type Error = AppError;
+
+        fn try_from((user_id, handle): (Uuid, &str)) -> Result<Self, Self::Error> {
+            let data = Self {
+                user_id,
+                handle: normalize(handle),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+}
+
+mod change_handle {
+    use uuid::Uuid;
+    use validator::Validate as _;
+
+    use crate::app::{
+        error::AppError,
+        users::{handle::normalize, service::change_handle::Request},
+    };
+
+    impl TryFrom<(Uuid, &str)> for Request {
+        type Error = AppError;
+
+        fn try_from((user_id, handle): (Uuid, &str)) -> Result<Self, Self::Error> {
+            let data = Self {
+                user_id,
+                handle: normalize(handle),
+            };
+            data.validate()?;
+
+            Ok(data)
+        }
+    }
+}
+
+async fn get_user_by_handle(
+    AppState { db, .. }: &AppState,
+    request: GetUserByHandleRequest,
+) -> Result<GetUserByHandleResponse, AppError> {
+    let user = service::get_user_by_handle(db, &handle::normalize(request.handle())).await?;
+
+    Ok(GetUserByHandleResponse {
+        user: Some(User::from(&user)),
+    })
+}
+
+async fn upload_avatar(
+    AppState {
+        db,
+        storage,
+        settings,
+        ..
+    }: &AppState,
+    user_id: Uuid,
+    request: UploadAvatarRequest,
+) -> Result<UploadAvatarResponse, AppError> {
+    let request = service::upload_avatar::Request {
+        user_id,
+        picture: request.picture.unwrap_or_default(),
+    };
+    let response = service::upload_avatar(db, storage, &settings.users, request).await?;
+
+    Ok(UploadAvatarResponse {
+        user: Some(User::from(&response.user)),
+    })
+}
+
+async fn update_preferences(
+    AppState { db, settings, .. }: &AppState,
+    user_id: Uuid,
+    request: UpdatePreferencesRequest,
+) -> Result<UpdatePreferencesResponse, AppError> {
+    let request = service::update_preferences::Request {
+        user_id,
+        ..request.try_into()?
+    };
+    let response = service::update_preferences(db, &settings.users, request).await?;
+
+    Ok(response.into())
+}
+
+mod preferences {
+    use flux_users_api::{
+        GetPreferencesResponse, Preferences, UpdatePreferencesRequest, UpdatePreferencesResponse,
+    };
+    use prost_types::{value::Kind, ListValue, Struct, Value};
+    use uuid::Uuid;
+    use validator::{ValidationError, ValidationErrors};
+
+    use crate::app::{
+        error::AppError,
+        users::{
+            preferences::{self, Theme},
+            service::{get_preferences::Response, update_preferences::Request},
+        },
+    };
+
+    impl TryFrom<UpdatePreferencesRequest> for Request {
+        type Error = AppError;
+
+        fn try_from(request: UpdatePreferencesRequest) -> Result<Self, Self::Error> {
+            let mut errors = ValidationErrors::new();
+
+            let version = request.version.unwrap_or_else(|| {
+                errors.add("version", ValidationError::new("required"));
+                0
+            });
+
+            let preferences = request.preferences.unwrap_or_default();
+            let theme = preferences.theme.as_deref().and_then(|value| {
+                let theme = Theme::parse(value);
+                if theme.is_none() {
+                    errors.add("theme", ValidationError::new("theme"));
+                }
+                theme
+            });
+
+            if !errors.is_empty() {
+                return Err(errors.into());
+            }
+
+            Ok(Self {
+                user_id: Uuid::nil(),
+                version,
+                preferences: preferences::Preferences {
+                    theme,
+                    notification_sound: preferences.notification_sound,
+                    compact_mode: preferences.compact_mode,
+                    custom: preferences
+                        .custom
+                        .map(|it| to_json_map(it.fields))
+                        .unwrap_or_default(),
+                },
+                paths: request
+                    .update_mask
+                    .map(|mask| mask.paths)
+                    .unwrap_or_default(),
+            })
+        }
+    }
+
+    impl From<Response> for GetPreferencesResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                preferences: Some(res.preferences.into()),
+                version: Some(res.version),
+            }
+        }
+    }
+
+    impl From<Response> for UpdatePreferencesResponse {
+        fn from(res: Response) -> Self {
+            Self {
+                preferences: Some(res.preferences.into()),
+                version: Some(res.version),
+            }
+        }
+    }
+
+    impl From<preferences::Preferences> for Preferences {
+        fn from(preferences: preferences::Preferences) -> Self {
+            Self {
+                theme: preferences.theme.map(|it| it.as_str().into()),
+                notification_sound: preferences.notification_sound,
+                compact_mode: preferences.compact_mode,
+                custom: Some(Struct {
+                    fields: preferences
+                        .custom
+                        .into_iter()
+                        .map(|(key, value)| (key, from_json(value)))
+                        .collect(),
+                }),
+            }
+        }
+    }
+
+    fn to_json_map<I>(fields: I) -> serde_json::Map<String, serde_json::Value>
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        fields
+            .into_iter()
+            .map(|(key, value)| (key, to_json(value)))
+            .collect()
+    }
+
+    // Struct numbers are doubles, non-finite ones have no JSON form and become null.
+    fn to_json(value: Value) -> serde_json::Value {
+        match value.kind {
+            None | Some(Kind::NullValue(_)) => serde_json::Value::Null,
+            Some(Kind::NumberValue(number)) => serde_json::Number::from_f64(number)
+                .map(serde_json::Value::Number)
+                .unwrap_or_default(),
+            Some(Kind::StringValue(string)) => serde_json::Value::String(string),
+            Some(Kind::BoolValue(bool)) => serde_json::Value::Bool(bool),
+            Some(Kind::StructValue(object)) => {
+                serde_json::Value::Object(to_json_map(object.fields))
+            }
+            Some(Kind::ListValue(list)) => {
+                serde_json::Value::Array(list.values.into_iter().map(to_json).collect())
+            }
+        }
+    }
+
+    fn from_json(value: serde_json::Value) -> Value {
+        let kind = match value {
+            serde_json::Value::Null => Kind::NullValue(0),
+            serde_json::Value::Bool(bool) => Kind::BoolValue(bool),
+            serde_json::Value::Number(number) => {
+                Kind::NumberValue(number.as_f64().unwrap_or_default())
+            }
+            serde_json::Value::String(string) => Kind::StringValue(string),
+            serde_json::Value::Array(values) => Kind::ListValue(ListValue {
+                values: values.into_iter().map(from_json).collect(),
+            }),
+            serde_json::Value::Object(object) => Kind::StructValue(Struct {
+                fields: object
+                    .into_iter()
+                    .map(|(key, value)| (key, from_json(value)))
+                    .collect(),
+            }),
+        };
+
+        Value { kind: Some(kind) }
     }
 }
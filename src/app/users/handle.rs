@@ -0,0 +1,123 @@
+use unicode_normalization::UnicodeNormalization as _;
+use validator::ValidationError;
+
+pub const MIN_LENGTH: usize = 3;
+pub const MAX_LENGTH: usize = 30;
+
+// Compared after folding, so "adm1n" or "Sup_port" are reserved as well.
+const RESERVED: &[&str] = &[
+    "admin",
+    "administrator",
+    "anonymous",
+    "api",
+    "channel",
+    "deleted",
+    "everyone",
+    "flux",
+    "help",
+    "here",
+    "mod",
+    "moderator",
+    "null",
+    "official",
+    "root",
+    "security",
+    "settings",
+    "staff",
+    "support",
+    "system",
+    "undefined",
+    "user",
+    "users",
+];
+
+// NFKC turns fullwidth and other compatibility forms into plain ASCII, a leading "@" is dropped.
+pub fn normalize(handle: &str) -> String {
+    let handle = handle.trim();
+
+    handle.strip_prefix('@').unwrap_or(handle).nfkc().collect()
+}
+
+// Skeleton used for uniqueness: handles that only differ in case, underscores or
+// lookalike characters fold to the same key.
+pub fn fold(handle: &str) -> String {
+    handle
+        .to_ascii_lowercase()
+        .replace('_', "")
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | 'i' => 'l',
+            '3' => 'e',
+            '4' => 'a',
+            '5' => 's',
+            c => c,
+        })
+        .collect()
+}
+
+pub fn validate(handle: &str) -> Result<(), ValidationError> {
+    let length = handle.chars().count();
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+        return Err(ValidationError::new("length"));
+    }
+
+    // ASCII only, which also rules out mixed-script lookalikes such as Cyrillic "а".
+    if !handle.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !handle
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(ValidationError::new("charset"));
+    }
+
+    if is_reserved(handle) {
+        return Err(ValidationError::new("reserved"));
+    }
+
+    Ok(())
+}
+
+fn is_reserved(handle: &str) -> bool {
+    let key = fold(handle);
+
+    RESERVED.iter().any(|reserved| fold(reserved) == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold, normalize, validate};
+
+    #[test]
+    fn should_normalize_handles() {
+        assert_eq!(normalize(" @zoe_k "), "zoe_k");
+        assert_eq!(normalize("\u{ff5a}\u{ff4f}\u{ff45}"), "zoe");
+    }
+
+    #[test]
+    fn should_fold_lookalikes() {
+        assert_eq!(fold("Zoe_K"), fold("zoek"));
+        assert_eq!(fold("b0b"), fold("bob"));
+        assert_eq!(fold("bill"), fold("b1ll"));
+        assert_eq!(fold("modern"), fold("modem"));
+        assert_ne!(fold("alice"), fold("alicia"));
+    }
+
+    #[test]
+    fn should_validate_handles() {
+        assert!(validate("zoe_k").is_ok());
+        assert!(validate("z0e").is_ok());
+
+        let code = |handle: &str| validate(handle).unwrap_err().code;
+        assert_eq!(code("zo"), "length");
+        assert_eq!(code(&"z".repeat(31)), "length");
+        assert_eq!(code("1zoe"), "charset");
+        assert_eq!(code("zoe-k"), "charset");
+        assert_eq!(code("\u{0437}oe"), "charset");
+        assert_eq!(code("Admin"), "reserved");
+        assert_eq!(code("adm1n"), "reserved");
+        assert_eq!(code("sup_port"), "reserved");
+    }
+}
@@ -0,0 +1,167 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse as _, Response},
+    routing::get,
+    Router,
+};
+use chrono::NaiveDateTime;
+use log::error;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::app::{error::AppError, state::AppState};
+
+use super::{
+    avatar::{self, Format, Theme},
+    repo, service,
+};
+
+const DEFAULT_SIZE: u32 = 128;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/users/{user_id}/avatar", get(get_avatar))
+}
+
+#[derive(Deserialize)]
+struct AvatarQuery {
+    #[serde(default)]
+    format: Format,
+    #[serde(default)]
+    theme: Theme,
+    size: Option<u32>,
+}
+
+// Public so it works in plain <img> tags. Only the initials and the color are exposed, the full
+// name stays out of the SVG, aria-label included.
+async fn get_avatar(
+    State(AppState { db, settings, .. }): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<AvatarQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let size = query.size.unwrap_or(DEFAULT_SIZE);
+    if !(avatar::MIN_SIZE..=avatar::MAX_SIZE).contains(&size) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let user = service::get_user(&db, user_id)
+        .await
+        .map_err(|err| match err {
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("users: avatar for {} failed: {}", user_id, err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    let etag = etag(user.updated_at, query.format, query.theme, size);
+    let cache_control = format!("public, max-age={}", settings.users.avatar.max_age);
+
+    if is_fresh(&headers, &etag) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+        )
+            .into_response());
+    }
+
+    let (initials, label) = if user.is_deleted() {
+        (
+            repo::user::DELETED_USER_ABBR.to_string(),
+            repo::user::DELETED_USER_NAME.to_string(),
+        )
+    } else {
+        (user.abbr(), user.abbr())
+    };
+    let svg = avatar::svg(&initials, &label, user.avatar_color(), query.theme, size);
+
+    let body = match query.format {
+        Format::Svg => svg.into_bytes(),
+        #[cfg(feature = "png")]
+        Format::Png => tokio::task::spawn_blocking(move || avatar::png(&svg, size))
+            .await
+            .map_err(|err| err.into())
+            .and_then(|it| it)
+            .map_err(|err: anyhow::Error| {
+                error!("users: avatar for {} failed: {}", user_id, err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+    };
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                query.format.content_type().to_string(),
+            ),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn etag(updated_at: NaiveDateTime, format: Format, theme: Theme, size: u32) -> String {
+    format!(
+        "\"{}-{:x}-{:?}-{:?}-{}\"",
+        avatar::VERSION,
+        updated_at.and_utc().timestamp_micros(),
+        format,
+        theme,
+        size
+    )
+    .to_lowercase()
+}
+
+fn is_fresh(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|it| it.to_str().ok())
+        .is_some_and(|it| {
+            it.split(',')
+                .map(|it| it.trim().trim_start_matches("W/"))
+                .any(|it| it == etag || it == "*")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+    use chrono::{Duration, Utc};
+
+    use crate::app::users::avatar::{Format, Theme};
+
+    use super::{etag, is_fresh};
+
+    #[test]
+    fn should_change_etag_with_rendering() {
+        let now = Utc::now().naive_utc();
+        let etag = |updated_at, theme, size| etag(updated_at, Format::Svg, theme, size);
+
+        assert_eq!(etag(now, Theme::Light, 64), etag(now, Theme::Light, 64));
+        assert_ne!(
+            etag(now, Theme::Light, 64),
+            etag(now + Duration::microseconds(1), Theme::Light, 64)
+        );
+        assert_ne!(etag(now, Theme::Light, 64), etag(now, Theme::Dark, 64));
+        assert_ne!(etag(now, Theme::Light, 64), etag(now, Theme::Light, 128));
+    }
+
+    #[test]
+    fn should_answer_not_modified_for_matching_etag() {
+        let etag = etag(Utc::now().naive_utc(), Format::Svg, Theme::Light, 64);
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+
+        assert!(is_fresh(&headers(&etag), &etag));
+        assert!(is_fresh(&headers(&format!("\"other\", W/{etag}")), &etag));
+        assert!(is_fresh(&headers("*"), &etag));
+        assert!(!is_fresh(&headers("\"other\""), &etag));
+        assert!(!is_fresh(&HeaderMap::new(), &etag));
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageDecoder as _, ImageFormat,
+    ImageReader, Limits, RgbImage,
+};
+use log::error;
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::app::{error::AppError, storage::Storage};
+
+// Square variants in pixels, DEFAULT_SIZE is the one exposed as avatar_url.
+pub const SIZES: [u32; 4] = [64, 128, 256, 512];
+pub const DEFAULT_SIZE: u32 = 256;
+
+pub const CONTENT_TYPE: &str = "image/jpeg";
+
+// Avatars are shown at 512px at most. Together the two limits keep a small, highly compressed
+// upload from expanding into gigabytes of pixels: an 8-bit RGBA frame at the maximum dimension
+// is 64 MiB, the rest is headroom for decoder buffers.
+const MAX_DIMENSION: u32 = 4096;
+const MAX_ALLOC: u64 = 128 * 1024 * 1024;
+const QUALITY: u8 = 85;
+
+pub fn key(user_id: Uuid, picture_id: Uuid, size: u32) -> String {
+    format!("pictures/{}/{}/{}.jpg", user_id, picture_id, size)
+}
+
+// Best effort: a leftover object is only wasted space, it is never referenced again.
+pub async fn delete(storage: &dyn Storage, user_id: Uuid, picture_id: Uuid) {
+    for size in SIZES {
+        let key = key(user_id, picture_id, size);
+        if let Err(err) = storage.delete(&key).await {
+            error!("users: failed to delete {}: {}", key, err);
+        }
+    }
+}
+
+// Decodes and re-encodes every variant from raw pixels, which leaves EXIF and any other
+// metadata behind. The EXIF orientation is applied first so rotated photos stay upright.
+pub fn variants(bytes: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, AppError> {
+    let format = image::guess_format(bytes).map_err(|_| invalid("format"))?;
+    if !matches!(
+        format,
+        ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP
+    ) {
+        return Err(invalid("format"));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(|_| invalid("format"))?;
+    let orientation = decoder.orientation().map_err(|_| invalid("format"))?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|_| invalid("format"))?;
+    image.apply_orientation(orientation);
+
+    let side = image.width().min(image.height());
+    let square = image.crop_imm(
+        (image.width() - side) / 2,
+        (image.height() - side) / 2,
+        side,
+        side,
+    );
+
+    SIZES
+        .iter()
+        .map(|&size| {
+            let variant = flatten(&square.resize_exact(size, size, FilterType::Lanczos3));
+
+            let mut buf = vec![];
+            JpegEncoder::new_with_quality(&mut buf, QUALITY)
+                .encode_image(&variant)
+                .map_err(anyhow::Error::from)?;
+
+            Ok((size, buf))
+        })
+        .collect()
+}
+
+// JPEG has no alpha channel, transparent areas end up white instead of black.
+fn flatten(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend =
+            |c: u8| ((u16::from(c) * u16::from(a) + 255 * (255 - u16::from(a))) / 255) as u8;
+
+        image::Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+fn invalid(code: &'static str) -> AppError {
+    let mut errors = ValidationErrors::new();
+    errors.add("picture", ValidationError::new(code));
+    errors.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+
+    use super::{variants, SIZES};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 0]));
+
+        let mut buf = Cursor::new(vec![]);
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut buf, ImageFormat::Png)
+            .unwrap();
+        buf.into_inner()
+    }
+
+    #[test]
+    fn should_generate_square_variants() {
+        let variants = variants(&png(300, 200)).unwrap();
+
+        assert_eq!(variants.len(), SIZES.len());
+        for (size, bytes) in variants {
+            let image = image::load_from_memory(&bytes).unwrap();
+            assert_eq!((image.width(), image.height()), (size, size));
+            // Transparent pixels are flattened onto white.
+            assert!(image.to_rgb8().get_pixel(0, 0).0.iter().all(|c| *c > 250));
+        }
+    }
+
+    #[test]
+    fn should_reject_oversized_pictures() {
+        assert!(variants(&png(4097, 16)).is_err());
+        assert!(variants(&png(16, 4097)).is_err());
+    }
+
+    #[test]
+    fn should_reject_unsupported_data() {
+        assert!(variants(b"GIF89a").is_err());
+        assert!(variants(b"not an image").is_err());
+    }
+}
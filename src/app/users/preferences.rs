@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use validator::{ValidationError, ValidationErrors};
+
+pub const NOTIFICATION_SOUNDS: &[&str] = &["none", "chime", "ding", "pop"];
+
+const CUSTOM_PREFIX: &str = "custom.";
+const MAX_CUSTOM_KEY_LENGTH: usize = 64;
+
+// Known keys are typed and validated. Anything else clients want to roam goes into `custom`,
+// which is only checked for key length and the overall size.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct Preferences {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification_sound: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compact_mode: Option<bool>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub custom: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "system" => Some(Self::System),
+            "light" => Some(Self::Light),
+            "dark" => Some(Self::Dark),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::Light => "light",
+            Self::Dark => "dark",
+        }
+    }
+}
+
+impl Preferences {
+    // Without paths every field set in `changes` is written and custom keys are merged, a null
+    // custom value removes the key. With paths only those are written and a missing value clears
+    // it, "custom" replaces the whole map and "custom.<key>" a single key.
+    pub fn apply(
+        &mut self,
+        changes: Preferences,
+        paths: &[String],
+    ) -> Result<(), ValidationErrors> {
+        let Preferences {
+            theme,
+            notification_sound,
+            compact_mode,
+            mut custom,
+        } = changes;
+
+        if paths.is_empty() {
+            self.theme = theme.or(self.theme);
+            self.notification_sound = notification_sound.or(self.notification_sound.take());
+            self.compact_mode = compact_mode.or(self.compact_mode);
+
+            for (key, value) in custom {
+                self.set_custom(key, Some(value));
+            }
+
+            return Ok(());
+        }
+
+        let mut errors = ValidationErrors::new();
+
+        for path in paths {
+            match path.as_str() {
+                "theme" => self.theme = theme,
+                "notification_sound" => self.notification_sound = notification_sound.clone(),
+                "compact_mode" => self.compact_mode = compact_mode,
+                "custom" => self.custom = std::mem::take(&mut custom),
+                path => match path.strip_prefix(CUSTOM_PREFIX) {
+                    Some(key) if !key.is_empty() => {
+                        self.set_custom(key.into(), custom.remove(key));
+                    }
+                    _ => errors.add("update_mask", ValidationError::new("unknown_field")),
+                },
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self, max_size: usize) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+
+        if let Some(sound) = &self.notification_sound {
+            if !NOTIFICATION_SOUNDS.contains(&sound.as_str()) {
+                errors.add(
+                    "notification_sound",
+                    ValidationError::new("notification_sound"),
+                );
+            }
+        }
+
+        if self
+            .custom
+            .keys()
+            .any(|key| key.is_empty() || key.len() > MAX_CUSTOM_KEY_LENGTH)
+        {
+            errors.add("custom", ValidationError::new("key"));
+        }
+
+        if serde_json::to_vec(self).map_or(true, |it| it.len() > max_size) {
+            errors.add("preferences", ValidationError::new("too_large"));
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(())
+    }
+
+    fn set_custom(&mut self, key: String, value: Option<Value>) {
+        match value {
+            Some(Value::Null) | None => self.custom.remove(&key),
+            Some(value) => self.custom.insert(key, value),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Preferences, Theme};
+
+    fn current() -> Preferences {
+        serde_json::from_value(json!({
+            "theme": "dark",
+            "compact_mode": true,
+            "custom": { "sidebar_width": 280, "pinned": ["a"] },
+        }))
+        .unwrap()
+    }
+
+    fn changes() -> Preferences {
+        serde_json::from_value(json!({
+            "notification_sound": "pop",
+            "custom": { "sidebar_width": 320, "pinned": null },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn should_merge_without_mask() {
+        let mut preferences = current();
+        preferences.apply(changes(), &[]).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&preferences).unwrap(),
+            json!({
+                "theme": "dark",
+                "notification_sound": "pop",
+                "compact_mode": true,
+                "custom": { "sidebar_width": 320 },
+            })
+        );
+    }
+
+    #[test]
+    fn should_apply_mask_paths() {
+        let mut preferences = current();
+        let paths = ["theme".into(), "custom.pinned".into()];
+        preferences.apply(changes(), &paths).unwrap();
+
+        assert_eq!(preferences.theme, None);
+        assert_eq!(preferences.notification_sound, None);
+        assert_eq!(preferences.compact_mode, Some(true));
+        assert_eq!(
+            preferences.custom,
+            json!({ "sidebar_width": 280 }).as_object().unwrap().clone()
+        );
+
+        assert!(current().apply(changes(), &["custom.".into()]).is_err());
+        assert!(current().apply(changes(), &["font".into()]).is_err());
+    }
+
+    #[test]
+    fn should_validate_preferences() {
+        assert!(current().validate(1024).is_ok());
+        assert!(current().validate(16).is_err());
+
+        let mut preferences = current();
+        preferences.notification_sound = Some("airhorn".into());
+        assert!(preferences.validate(1024).is_err());
+
+        assert_eq!(Theme::parse("light"), Some(Theme::Light));
+        assert_eq!(Theme::parse("sepia"), None);
+    }
+}
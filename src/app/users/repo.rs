@@ -1,10 +1,13 @@
 use anyhow::Error;
 use sea_orm::{
-    ColumnTrait as _, ConnectionTrait, EntityTrait as _, Order, QueryFilter as _, QueryOrder as _,
+    ActiveModelTrait as _, ColumnTrait as _, ConnectionTrait, DbBackend, DbErr, EntityTrait as _,
+    FromQueryResult as _, IntoActiveModel as _, Order, QueryFilter as _, QueryOrder as _,
+    QuerySelect as _, Statement,
 };
 use uuid::Uuid;
 
 pub mod user;
+pub mod user_preference;
 
 pub async fn find_users_by_ids<T: ConnectionTrait>(
     db: &T,
@@ -18,3 +21,151 @@ pub async fn find_users_by_ids<T: ConnectionTrait>(
 
     Ok(users)
 }
+
+// The expressions match the trigram indexes on users. $1 is the lowercased query,
+// $2 the same as a LIKE pattern and $3 whether email is searched at all. Results after the
+// ($4 rank, $5 id) keyset come best match first, equal ranks in id order.
+const SEARCH_USERS_SQL: &str = "\
+    SELECT * FROM ( \
+        SELECT users.*, GREATEST( \
+            word_similarity($1, lower(first_name || ' ' || last_name)), \
+            similarity($1, lower(handle)), \
+            CASE WHEN $3 THEN similarity($1, lower(email)) ELSE 0 END \
+        )::real AS rank \
+        FROM users \
+        WHERE deleted_at IS NULL AND ( \
+            lower(first_name || ' ' || last_name) LIKE $2 \
+            OR lower(handle) LIKE $2 \
+            OR ($3 AND lower(email) LIKE $2) \
+        ) \
+    ) AS matches \
+    WHERE $4::real IS NULL OR rank < $4 OR (rank = $4 AND id > $5) \
+    ORDER BY rank DESC, id ASC \
+    LIMIT $6";
+
+pub async fn search_users<T: ConnectionTrait>(
+    db: &T,
+    query: &str,
+    email: bool,
+    after: Option<(f32, Uuid)>,
+    limit: u64,
+) -> Result<Vec<(user::Model, f32)>, DbErr> {
+    let pattern = format!(
+        "%{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    db.query_all(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        SEARCH_USERS_SQL,
+        [
+            query.into(),
+            pattern.into(),
+            email.into(),
+            after.map(|(rank, _)| rank).into(),
+            after.map(|(_, id)| id).into(),
+            (limit as i64).into(),
+        ],
+    ))
+    .await?
+    .iter()
+    .map(|row| {
+        Ok((
+            user::Model::from_query_result(row, "")?,
+            row.try_get("", "rank")?,
+        ))
+    })
+    .collect()
+}
+
+pub async fn find_user_by_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Option<user::Model>, DbErr> {
+    user::Entity::find_by_id(user_id).one(db).await
+}
+
+pub async fn find_user_by_id_with_lock<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Option<user::Model>, DbErr> {
+    user::Entity::find_by_id(user_id)
+        .filter(user::Column::DeletedAt.is_null())
+        .lock_exclusive()
+        .one(db)
+        .await
+}
+
+pub async fn update_user<T: ConnectionTrait>(
+    db: &T,
+    model: user::ActiveModel,
+) -> Result<user::Model, DbErr> {
+    model.update(db).await
+}
+
+// Includes deleted users: their handle stays taken until the account is purged.
+pub async fn find_user_by_handle_key<T: ConnectionTrait>(
+    db: &T,
+    handle_key: &str,
+) -> Result<Option<user::Model>, DbErr> {
+    user::Entity::find()
+        .filter(user::Column::HandleKey.eq(handle_key))
+        .one(db)
+        .await
+}
+
+// Unique index on the folded handle, see users_handle_key_idx in the migrations.
+pub const USERS_HANDLE_INDEX: &str = "users_handle_key_idx";
+
+pub async fn update_user_handle<T: ConnectionTrait>(
+    db: &T,
+    model: user::ActiveModel,
+) -> Result<user::Model, DbErr> {
+    model.update(db).await
+}
+
+pub async fn find_user_preference_by_user_id<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Option<user_preference::Model>, DbErr> {
+    user_preference::Entity::find_by_id(user_id).one(db).await
+}
+
+pub async fn find_user_preference_by_user_id_with_lock<T: ConnectionTrait>(
+    db: &T,
+    user_id: Uuid,
+) -> Result<Option<user_preference::Model>, DbErr> {
+    user_preference::Entity::find_by_id(user_id)
+        .lock_exclusive()
+        .one(db)
+        .await
+}
+
+pub async fn create_user_preference<T: ConnectionTrait>(
+    db: &T,
+    model: user_preference::Model,
+) -> Result<user_preference::Model, DbErr> {
+    model.into_active_model().insert(db).await
+}
+
+pub async fn update_user_preference<T: ConnectionTrait>(
+    db: &T,
+    model: user_preference::ActiveModel,
+) -> Result<user_preference::Model, DbErr> {
+    model.update(db).await
+}
+
+pub async fn delete_user_preferences_by_user_ids<T: ConnectionTrait>(
+    db: &T,
+    user_ids: &[Uuid],
+) -> Result<(), DbErr> {
+    user_preference::Entity::delete_many()
+        .filter(user_preference::Column::UserId.is_in(user_ids.to_vec()))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
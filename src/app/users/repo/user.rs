@@ -1,6 +1,10 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::app::{color, name};
+
+pub use crate::app::auth::repo::user::Status;
+
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "users")]
 pub struct Model {
@@ -9,32 +13,52 @@ pub struct Model {
     pub first_name: String,
     pub last_name: String,
     pub locale: Option<String>,
+    pub updated_at: DateTime,
+    pub deleted_at: Option<DateTime>,
+    pub status: Status,
+    pub status_reason: Option<String>,
+    pub status_until: Option<DateTime>,
+    pub handle: Option<String>,
+    pub handle_key: Option<String>,
+    pub handle_changed_at: Option<DateTime>,
+    pub preferred_color: Option<String>,
+    pub picture_id: Option<Uuid>,
+    pub picture_url: Option<String>,
+    pub timezone: Option<String>,
 }
 
+pub const DELETED_USER_NAME: &str = "Deleted user";
+pub const DELETED_USER_ABBR: &str = "DU";
+
 impl Model {
-    pub fn name(&self) -> String {
-        [self.first_name.clone(), self.last_name.clone()].join(" ")
+    pub fn status_at(&self, now: DateTime) -> Status {
+        self.status.at(self.status_until, now)
     }
 
-    pub fn abbr(&self) -> String {
-        let mut abbr = String::from("");
-
-        if let Some(l) = self.first_name.chars().take(1).last() {
-            abbr.push(l);
-        }
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
 
-        if let Some(l) = self.last_name.chars().take(1).last() {
-            abbr.push(l);
-        }
+    pub fn name(&self) -> String {
+        name::display_name(
+            &self.first_name,
+            &self.last_name,
+            self.handle.as_deref(),
+            self.locale.as_deref(),
+        )
+    }
 
-        abbr
+    pub fn abbr(&self) -> String {
+        name::initials(
+            &self.first_name,
+            &self.last_name,
+            self.handle.as_deref(),
+            self.locale.as_deref(),
+        )
     }
 
-    pub fn color(&self) -> String {
-        random_color::RandomColor::new()
-            .alpha(0.3)
-            .seed(self.id.to_string())
-            .to_hsla_string()
+    pub fn avatar_color(&self) -> &'static color::Color {
+        color::for_user(self.id, self.preferred_color.as_deref())
     }
 }
 
@@ -1,13 +1,249 @@
+use std::collections::{HashMap, HashSet};
+
 use anyhow::Error;
-use sea_orm::DbConn;
+use chrono::{Duration, NaiveDateTime, Utc};
+use sea_orm::{ConnectionTrait, DbConn, IntoActiveModel as _, Set, SqlErr, TransactionTrait as _};
+use serde_json::json;
+use tokio::sync::mpsc;
 use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::app::{
+    audit::{self, action},
+    error::AppError,
+    locale::{self, LocaleSettings},
+    storage::SharedStorage,
+};
+
+use super::{
+    cursor, error::UsersError, handle, picture, preferences::Preferences, repo,
+    settings::UsersSettings, watch,
+};
+
+// Users come back in the order they were asked for, duplicates only once. Ids without a user
+// are listed in missing_user_ids instead of being dropped.
+pub async fn get_users(
+    db: &DbConn,
+    settings: &UsersSettings,
+    request: GetUsersRequest,
+) -> Result<GetUsersResponse, AppError> {
+    let user_ids = distinct(request.user_ids, settings.get_users.max_batch_size)?;
+
+    let mut response = GetUsersResponse {
+        users: vec![],
+        missing_user_ids: vec![],
+    };
+
+    if user_ids.is_empty() {
+        return Ok(response);
+    }
+
+    let mut users: HashMap<Uuid, repo::user::Model> = repo::find_users_by_ids(db, user_ids.clone())
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+
+    for user_id in user_ids {
+        match users.remove(&user_id) {
+            Some(user) => response.users.push(user),
+            None => response.missing_user_ids.push(user_id),
+        }
+    }
+
+    Ok(response)
+}
+
+fn distinct(user_ids: Vec<Uuid>, max: usize) -> Result<Vec<Uuid>, AppError> {
+    let mut seen = HashSet::new();
+    let user_ids: Vec<Uuid> = user_ids
+        .into_iter()
+        .filter(|user_id| seen.insert(*user_id))
+        .collect();
+
+    if user_ids.len() > max {
+        let mut error = ValidationError::new("length");
+        error.add_param("max".into(), &max);
+
+        let mut errors = ValidationErrors::new();
+        errors.add("user_ids", error);
+        return Err(errors.into());
+    }
+
+    Ok(user_ids)
+}
+
+pub fn watch_users(
+    db: &DbConn,
+    hub: &watch::Hub,
+    settings: &UsersSettings,
+    request: GetUsersRequest,
+) -> Result<mpsc::Receiver<Result<repo::user::Model, AppError>>, AppError> {
+    let user_ids = distinct(request.user_ids, settings.watch.max_user_ids)?;
+
+    if user_ids.is_empty() {
+        let mut errors = ValidationErrors::new();
+        errors.add("user_ids", ValidationError::new("required"));
+        return Err(errors.into());
+    }
+
+    Ok(watch::watch(db.clone(), hub, user_ids))
+}
+
+// Deleted users are returned too, callers render them as a placeholder.
+pub async fn get_user(db: &DbConn, user_id: Uuid) -> Result<repo::user::Model, AppError> {
+    repo::find_user_by_id(db, user_id)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+// Deleted users are never found. Email only matches for callers allowed to search by it, so it
+// can't be probed through the ranking either.
+pub async fn search_users(
+    db: &DbConn,
+    settings: &UsersSettings,
+    request: search_users::Request,
+) -> Result<search_users::Response, AppError> {
+    use search_users::{Cursor, Response};
+
+    let after = match request.page_token.as_deref().filter(|it| !it.is_empty()) {
+        Some(token) => {
+            let cursor: Cursor = cursor::decode(token)?;
+            // A token only continues the query it was issued for, searched the same way.
+            if cursor.query != request.query || cursor.email != request.email {
+                return Err(cursor::invalid().into());
+            }
+            Some((cursor.rank, cursor.id))
+        }
+        None => None,
+    };
+
+    let page_size = cursor::page_size(
+        request.page_size,
+        settings.search.default_page_size,
+        settings.search.max_page_size,
+    );
+
+    let mut users =
+        repo::search_users(db, &request.query, request.email, after, page_size + 1).await?;
+
+    let next_page_token = cursor::next_page(&mut users, page_size, |(user, rank)| Cursor {
+        query: request.query.clone(),
+        email: request.email,
+        rank: *rank,
+        id: user.id,
+    })?;
+
+    Ok(Response {
+        users: users.into_iter().map(|(user, _)| user).collect(),
+        next_page_token,
+    })
+}
+
+pub mod search_users {
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+    use validator::Validate;
+
+    use crate::app::users::repo;
+
+    // pg_trgm indexes can't narrow down anything shorter than a trigram, a shorter query would
+    // scan every user.
+    #[derive(Validate)]
+    pub struct Request {
+        #[validate(length(min = 3, max = 100))]
+        pub query: String,
+        pub page_size: u64,
+        pub page_token: Option<String>,
+        pub email: bool,
+    }
+
+    pub struct Response {
+        pub users: Vec<repo::user::Model>,
+        pub next_page_token: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Cursor {
+        pub query: String,
+        pub email: bool,
+        pub rank: f32,
+        pub id: Uuid,
+    }
+
+    // Matching is case-insensitive, a leading "@" searches handles the way they are shown.
+    pub fn normalize(query: &str) -> String {
+        let query = query.trim();
+
+        query
+            .strip_prefix('@')
+            .unwrap_or(query)
+            .trim()
+            .to_lowercase()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use uuid::Uuid;
+        use validator::Validate as _;
+
+        use crate::app::{
+            state::AppState,
+            users::{cursor, service::search_users},
+        };
+
+        use super::{normalize, Cursor, Request};
+
+        fn request(query: &str) -> Request {
+            Request {
+                query: normalize(query),
+                page_size: 0,
+                page_token: None,
+                email: false,
+            }
+        }
+
+        #[test]
+        fn should_normalize_queries() {
+            assert_eq!(normalize("  @Zoe_K "), "zoe_k");
+            assert_eq!(normalize("Zoë Kravitz"), "zoë kravitz");
+        }
+
+        #[test]
+        fn should_validate_query_length() {
+            assert!(request("zoe").validate().is_ok());
+            assert!(request("zo").validate().is_err());
+            assert!(request("@zo").validate().is_err());
+            assert!(request(&"a".repeat(101)).validate().is_err());
+        }
 
-use super::repo;
+        #[tokio::test]
+        async fn should_reject_token_for_another_query() {
+            let settings = &AppState::default().settings.users;
 
-pub async fn get_users(db: &DbConn, request: GetUsersRequest) -> Result<GetUsersResponse, Error> {
-    let users = repo::find_users_by_ids(db, request.user_ids).await?;
+            for (query, email) in [("zoey", false), ("zoe", true)] {
+                let cursor = Cursor {
+                    query: query.into(),
+                    email,
+                    rank: 0.5,
+                    id: Uuid::now_v7(),
+                };
 
-    Ok(GetUsersResponse { users })
+                cursor::assert_rejects_token(&cursor, |db, page_token| async move {
+                    search_users(
+                        &db,
+                        settings,
+                        Request {
+                            page_token: Some(page_token),
+                            ..request("zoe")
+                        },
+                    )
+                    .await
+                })
+                .await;
+            }
+        }
+    }
 }
 
 pub struct GetUsersRequest {
@@ -16,4 +252,565 @@ pub struct GetUsersRequest {
 
 pub struct GetUsersResponse {
     pub users: Vec<repo::user::Model>,
+    pub missing_user_ids: Vec<Uuid>,
+}
+
+pub async fn update_profile(
+    db: &DbConn,
+    locales: &LocaleSettings,
+    request: update_profile::Request,
+) -> Result<update_profile::Response, AppError> {
+    let txn = db.begin().await?;
+
+    let user = repo::find_user_by_id_with_lock(&txn, request.user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let mut model = user.into_active_model();
+    let mut fields = vec![];
+
+    if let Some(first_name) = request.first_name {
+        model.first_name = Set(first_name);
+        fields.push("first_name");
+    }
+
+    if let Some(last_name) = request.last_name {
+        model.last_name = Set(last_name);
+        fields.push("last_name");
+    }
+
+    if let Some(value) = request.locale {
+        let value = locale::resolve(locales, &value).ok_or_else(|| {
+            let mut errors = ValidationErrors::new();
+            errors.add("locale", ValidationError::new("unsupported"));
+            errors
+        })?;
+        model.locale = Set(Some(value));
+        fields.push("locale");
+    }
+
+    if let Some(preferred_color) = request.preferred_color {
+        model.preferred_color = Set(Some(preferred_color).filter(|it| !it.is_empty()));
+        fields.push("preferred_color");
+    }
+
+    if let Some(timezone) = request.timezone {
+        model.timezone = Set(locale::timezone(&timezone).map(Into::into));
+        fields.push("timezone");
+    }
+
+    model.updated_at = Set(Utc::now().naive_utc());
+
+    let user = repo::update_user(&txn, model).await?;
+    watch::notify(&txn, user.id).await?;
+
+    audit::record(
+        &txn,
+        user.id,
+        action::PROFILE_UPDATED,
+        json!({ "fields": fields }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(update_profile::Response { user })
+}
+
+pub mod update_profile {
+    use unicode_normalization::UnicodeNormalization as _;
+    use uuid::Uuid;
+    use validator::{Validate, ValidationError};
+
+    use crate::app::{color, locale, users::repo};
+
+    pub const FIELDS: [&str; 5] = [
+        "first_name",
+        "last_name",
+        "locale",
+        "preferred_color",
+        "timezone",
+    ];
+
+    #[derive(Validate)]
+    pub struct Request {
+        pub user_id: Uuid,
+        #[validate(length(min = 1, max = 100), custom(function = "validate_text"))]
+        pub first_name: Option<String>,
+        #[validate(length(min = 1, max = 100), custom(function = "validate_text"))]
+        pub last_name: Option<String>,
+        #[validate(length(min = 2, max = 35), custom(function = "validate_text"))]
+        pub locale: Option<String>,
+        #[validate(custom(function = "validate_preferred_color"))]
+        pub preferred_color: Option<String>,
+        #[validate(custom(function = "locale::validate_timezone"))]
+        pub timezone: Option<String>,
+    }
+
+    pub struct Response {
+        pub user: repo::user::Model,
+    }
+
+    // NFC so that visually identical names compare and sort the same way.
+    pub fn normalize(value: &str) -> String {
+        value.trim().nfc().collect()
+    }
+
+    fn validate_text(value: &str) -> Result<(), ValidationError> {
+        if value.chars().any(char::is_control) {
+            return Err(ValidationError::new("control_characters"));
+        }
+
+        Ok(())
+    }
+
+    fn validate_preferred_color(value: &str) -> Result<(), ValidationError> {
+        if !value.is_empty() && color::find(value).is_none() {
+            return Err(ValidationError::new("unknown_color"));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use uuid::Uuid;
+        use validator::Validate as _;
+
+        use super::{normalize, Request};
+
+        #[test]
+        fn should_normalize_to_nfc() {
+            assert_eq!(normalize("  Zoe\u{0301} "), "Zo\u{00e9}");
+        }
+
+        #[test]
+        fn should_validate_names() {
+            let request = |first_name: &str| Request {
+                user_id: Uuid::now_v7(),
+                first_name: Some(first_name.into()),
+                last_name: None,
+                locale: None,
+                preferred_color: None,
+                timezone: None,
+            };
+
+            assert!(request("Zoé").validate().is_ok());
+            assert!(request("").validate().is_err());
+            assert!(request("Zo\u{0007}e").validate().is_err());
+            assert!(request(&"a".repeat(101)).validate().is_err());
+        }
+    }
+}
+
+pub async fn check_handle_availability(
+    db: &DbConn,
+    request: check_handle_availability::Request,
+) -> Result<check_handle_availability::Response, AppError> {
+    use check_handle_availability::{Availability, Response};
+
+    let availability = match handle::validate(&request.handle) {
+        Err(error) if error.code == "reserved" => Availability::Reserved,
+        Err(_) => Availability::Invalid,
+        // The caller's own handle counts as available, so re-casing it is not reported as taken.
+        Ok(()) => match repo::find_user_by_handle_key(db, &handle::fold(&request.handle)).await? {
+            Some(user) if user.id != request.user_id => Availability::Taken,
+            _ => Availability::Available,
+        },
+    };
+
+    Ok(Response {
+        handle: request.handle,
+        availability,
+    })
+}
+
+pub mod check_handle_availability {
+    use uuid::Uuid;
+
+    pub struct Request {
+        pub user_id: Uuid,
+        pub handle: String,
+    }
+
+    pub struct Response {
+        pub handle: String,
+        pub availability: Availability,
+    }
+
+    pub enum Availability {
+        Available,
+        Invalid,
+        Reserved,
+        Taken,
+    }
+}
+
+pub async fn claim_handle(
+    db: &DbConn,
+    request: claim_handle::Request,
+) -> Result<claim_handle::Response, AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let user = repo::find_user_by_id_with_lock(&txn, request.user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if user.handle.is_some() {
+        return Err(UsersError::HandleAlreadyClaimed.into());
+    }
+
+    let user = set_handle(&txn, user, &request.handle, now).await?;
+
+    audit::record(
+        &txn,
+        user.id,
+        action::HANDLE_CLAIMED,
+        json!({ "handle": request.handle }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(claim_handle::Response { user })
+}
+
+pub mod claim_handle {
+    use uuid::Uuid;
+    use validator::Validate;
+
+    use crate::app::users::{handle, repo};
+
+    #[derive(Validate)]
+    pub struct Request {
+        pub user_id: Uuid,
+        #[validate(custom(function = "handle::validate"))]
+        pub handle: String,
+    }
+
+    pub struct Response {
+        pub user: repo::user::Model,
+    }
+}
+
+pub async fn change_handle(
+    db: &DbConn,
+    settings: &UsersSettings,
+    request: change_handle::Request,
+) -> Result<change_handle::Response, AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let user = repo::find_user_by_id_with_lock(&txn, request.user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let previous = user.handle.clone().ok_or(UsersError::HandleNotClaimed)?;
+
+    if previous == request.handle {
+        let mut errors = ValidationErrors::new();
+        errors.add("handle", ValidationError::new("unchanged"));
+        return Err(errors.into());
+    }
+
+    if let Some(changed_at) = user.handle_changed_at {
+        let available_at = changed_at + Duration::seconds(settings.handle.change_cooldown);
+        if available_at > now {
+            return Err(UsersError::CooldownActive(available_at).into());
+        }
+    }
+
+    let user = set_handle(&txn, user, &request.handle, now).await?;
+
+    audit::record(
+        &txn,
+        user.id,
+        action::HANDLE_CHANGED,
+        json!({ "from": previous, "to": request.handle }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(change_handle::Response { user })
+}
+
+pub mod change_handle {
+    use uuid::Uuid;
+    use validator::Validate;
+
+    use crate::app::users::{handle, repo};
+
+    #[derive(Validate)]
+    pub struct Request {
+        pub user_id: Uuid,
+        #[validate(custom(function = "handle::validate"))]
+        pub handle: String,
+    }
+
+    pub struct Response {
+        pub user: repo::user::Model,
+    }
+}
+
+async fn set_handle<T: ConnectionTrait>(
+    db: &T,
+    user: repo::user::Model,
+    value: &str,
+    now: NaiveDateTime,
+) -> Result<repo::user::Model, AppError> {
+    let mut model = user.into_active_model();
+    model.handle = Set(Some(value.into()));
+    model.handle_key = Set(Some(handle::fold(value)));
+    model.handle_changed_at = Set(Some(now));
+    model.updated_at = Set(now);
+
+    // Lookalikes fold to the same key, so a handle can be taken under a different spelling.
+    let user = repo::update_user_handle(db, model)
+        .await
+        .map_err(|err| match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(message))
+                if message.contains(repo::USERS_HANDLE_INDEX) =>
+            {
+                UsersError::HandleUnavailable.into()
+            }
+            _ => AppError::from(err),
+        })?;
+    watch::notify(db, user.id).await?;
+
+    Ok(user)
+}
+
+pub async fn get_user_by_handle(db: &DbConn, value: &str) -> Result<repo::user::Model, AppError> {
+    repo::find_user_by_handle_key(db, &handle::fold(value))
+        .await?
+        .filter(|user| !user.is_deleted())
+        .ok_or(AppError::NotFound)
+}
+
+pub async fn upload_avatar(
+    db: &DbConn,
+    storage: &SharedStorage,
+    settings: &UsersSettings,
+    request: upload_avatar::Request,
+) -> Result<upload_avatar::Response, AppError> {
+    if request.picture.len() > settings.avatar.max_upload_size {
+        let mut errors = ValidationErrors::new();
+        errors.add("picture", ValidationError::new("too_large"));
+        return Err(errors.into());
+    }
+
+    let variants = tokio::task::spawn_blocking(move || picture::variants(&request.picture))
+        .await
+        .map_err(Error::from)??;
+
+    let user_id = request.user_id;
+    let picture_id = Uuid::now_v7();
+
+    for (size, bytes) in variants {
+        let key = picture::key(user_id, picture_id, size);
+        if let Err(err) = storage.put(&key, picture::CONTENT_TYPE, bytes).await {
+            picture::delete(storage.as_ref(), user_id, picture_id).await;
+            return Err(err.into());
+        }
+    }
+
+    let url = storage.url(&picture::key(user_id, picture_id, picture::DEFAULT_SIZE));
+
+    match set_picture(
+        db,
+        user_id,
+        Some((picture_id, url)),
+        action::AVATAR_UPLOADED,
+    )
+    .await
+    {
+        Ok((user, previous)) => {
+            if let Some(previous) = previous {
+                picture::delete(storage.as_ref(), user_id, previous).await;
+            }
+
+            Ok(upload_avatar::Response { user })
+        }
+        Err(err) => {
+            picture::delete(storage.as_ref(), user_id, picture_id).await;
+            Err(err)
+        }
+    }
+}
+
+pub mod upload_avatar {
+    use uuid::Uuid;
+
+    use crate::app::users::repo;
+
+    pub struct Request {
+        pub user_id: Uuid,
+        pub picture: Vec<u8>,
+    }
+
+    pub struct Response {
+        pub user: repo::user::Model,
+    }
+}
+
+pub async fn delete_avatar(
+    db: &DbConn,
+    storage: &SharedStorage,
+    user_id: Uuid,
+) -> Result<upload_avatar::Response, AppError> {
+    let (user, previous) = set_picture(db, user_id, None, action::AVATAR_DELETED).await?;
+
+    if let Some(previous) = previous {
+        picture::delete(storage.as_ref(), user_id, previous).await;
+    }
+
+    Ok(upload_avatar::Response { user })
+}
+
+// Objects are only deleted once the row no longer points at them, the caller does that with
+// the returned previous picture id.
+async fn set_picture(
+    db: &DbConn,
+    user_id: Uuid,
+    picture: Option<(Uuid, String)>,
+    action: &str,
+) -> Result<(repo::user::Model, Option<Uuid>), AppError> {
+    let txn = db.begin().await?;
+
+    let user = repo::find_user_by_id_with_lock(&txn, user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let previous = user.picture_id;
+
+    let (picture_id, picture_url) = picture.unzip();
+
+    let mut model = user.into_active_model();
+    model.picture_id = Set(picture_id);
+    model.picture_url = Set(picture_url);
+    model.updated_at = Set(Utc::now().naive_utc());
+
+    let user = repo::update_user(&txn, model).await?;
+    watch::notify(&txn, user.id).await?;
+
+    audit::record(&txn, user.id, action, json!({ "picture_id": picture_id })).await?;
+
+    txn.commit().await?;
+
+    Ok((user, previous))
+}
+
+pub async fn get_preferences(
+    db: &DbConn,
+    user_id: Uuid,
+) -> Result<get_preferences::Response, AppError> {
+    let response = match repo::find_user_preference_by_user_id(db, user_id).await? {
+        Some(model) => get_preferences::Response {
+            preferences: serde_json::from_value(model.data)?,
+            version: model.version,
+        },
+        None => get_preferences::Response {
+            preferences: Preferences::default(),
+            version: 0,
+        },
+    };
+
+    Ok(response)
+}
+
+pub mod get_preferences {
+    use crate::app::users::preferences::Preferences;
+
+    // Version 0 means nothing has been stored yet.
+    pub struct Response {
+        pub preferences: Preferences,
+        pub version: i64,
+    }
+}
+
+pub async fn update_preferences(
+    db: &DbConn,
+    settings: &UsersSettings,
+    request: update_preferences::Request,
+) -> Result<get_preferences::Response, AppError> {
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+
+    let current = repo::find_user_preference_by_user_id_with_lock(&txn, request.user_id).await?;
+
+    let version = current.as_ref().map_or(0, |model| model.version);
+    if version != request.version {
+        return Err(UsersError::PreferencesVersionMismatch(version).into());
+    }
+
+    let mut preferences: Preferences = match &current {
+        Some(model) => serde_json::from_value(model.data.clone())?,
+        None => Preferences::default(),
+    };
+    preferences.apply(request.preferences, &request.paths)?;
+    preferences.validate(settings.preferences.max_size)?;
+
+    let data = serde_json::to_value(&preferences)?;
+
+    let model = match current {
+        Some(model) => {
+            let mut model = model.into_active_model();
+            model.data = Set(data);
+            model.version = Set(version + 1);
+            model.updated_at = Set(now);
+
+            repo::update_user_preference(&txn, model).await?
+        }
+        None => {
+            let created = repo::create_user_preference(
+                &txn,
+                repo::user_preference::Model {
+                    user_id: request.user_id,
+                    data,
+                    version: 1,
+                    created_at: now,
+                    updated_at: now,
+                },
+            )
+            .await;
+
+            match created {
+                Ok(model) => model,
+                // Two first writes race on the primary key, the loser is told the version the
+                // winner left behind.
+                Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                    txn.rollback().await?;
+
+                    let version = repo::find_user_preference_by_user_id(db, request.user_id)
+                        .await?
+                        .map_or(0, |model| model.version);
+
+                    return Err(UsersError::PreferencesVersionMismatch(version).into());
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    };
+
+    txn.commit().await?;
+
+    Ok(get_preferences::Response {
+        preferences,
+        version: model.version,
+    })
+}
+
+pub mod update_preferences {
+    use uuid::Uuid;
+
+    use crate::app::users::preferences::Preferences;
+
+    pub struct Request {
+        pub user_id: Uuid,
+        // Version the client last read, the update is rejected if it has moved on since.
+        pub version: i64,
+        pub preferences: Preferences,
+        pub paths: Vec<String>,
+    }
 }
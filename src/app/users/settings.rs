@@ -0,0 +1,56 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct UsersSettings {
+    pub handle: HandleSettings,
+    pub avatar: AvatarSettings,
+    pub preferences: PreferencesSettings,
+    pub get_users: GetUsersSettings,
+    pub watch: WatchSettings,
+    pub search: SearchSettings,
+    pub list: ListSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct HandleSettings {
+    pub change_cooldown: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AvatarSettings {
+    pub max_age: u64,
+    pub max_upload_size: usize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct PreferencesSettings {
+    // Serialized JSON, custom keys included.
+    pub max_size: usize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct GetUsersSettings {
+    pub max_batch_size: usize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WatchSettings {
+    pub max_user_ids: usize,
+    // Changes buffered per process before slow WatchUsers streams fall behind and get a resend.
+    pub capacity: usize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SearchSettings {
+    // Used when a request leaves page_size unset, larger ones are lowered to max_page_size.
+    pub default_page_size: u64,
+    pub max_page_size: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ListSettings {
+    pub default_page_size: u64,
+    pub max_page_size: u64,
+    // Rows read per query while ExportUsers streams.
+    pub export_batch_size: u64,
+}
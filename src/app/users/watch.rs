@@ -0,0 +1,275 @@
+use std::{collections::HashSet, future::Future};
+
+use anyhow::Error;
+use log::warn;
+use sea_orm::{sqlx::postgres::PgListener, ConnectionTrait, DbBackend, DbConn, DbErr, Statement};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc,
+};
+use uuid::Uuid;
+
+use crate::app::error::AppError;
+
+use super::repo;
+
+const CHANNEL: &str = "users_changed";
+
+// Fans changed user ids out to every WatchUsers stream of this process. It is only fed by the
+// LISTEN loop, so changes made here and on other replicas arrive the same way, after commit.
+#[derive(Clone)]
+pub struct Hub {
+    sender: broadcast::Sender<Uuid>,
+}
+
+impl Hub {
+    pub fn new(capacity: usize) -> Self {
+        // broadcast::channel panics on a zero capacity, which would come straight from config.
+        let (sender, _) = broadcast::channel(capacity.max(1));
+
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Uuid> {
+        self.sender.subscribe()
+    }
+
+    fn publish(&self, user_id: Uuid) {
+        // Nobody watching is not an error.
+        let _ = self.sender.send(user_id);
+    }
+}
+
+// Call inside the transaction that changes what GetUsers returns for the user. Postgres only
+// delivers the notification once that transaction commits, and drops it on rollback.
+pub async fn notify<T: ConnectionTrait>(db: &T, user_id: Uuid) -> Result<(), DbErr> {
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_notify($1, $2)",
+        [CHANNEL.into(), user_id.to_string().into()],
+    ))
+    .await?;
+
+    Ok(())
+}
+
+// Returns only on error. PgListener reconnects on its own, notifications sent while it was
+// disconnected are lost.
+pub async fn listen(db: &DbConn, hub: &Hub) -> Result<(), Error> {
+    let mut listener = PgListener::connect_with(db.get_postgres_connection_pool()).await?;
+    listener.listen(CHANNEL).await?;
+
+    loop {
+        let notification = listener.recv().await?;
+
+        match Uuid::parse_str(notification.payload()) {
+            Ok(user_id) => hub.publish(user_id),
+            Err(_) => warn!(
+                "users: ignoring {} payload {}",
+                CHANNEL,
+                notification.payload()
+            ),
+        }
+    }
+}
+
+// Sends the current state of every watched user first, then each user again whenever it
+// changes. A subscriber that falls behind gets everything resent instead of the missed ids.
+//
+// Only writes notify. A timed suspension lapses without one, so the status a watcher was last
+// sent stays "suspended" until something else about the user changes.
+pub fn watch(
+    db: DbConn,
+    hub: &Hub,
+    user_ids: Vec<Uuid>,
+) -> mpsc::Receiver<Result<repo::user::Model, AppError>> {
+    spawn(hub, user_ids, move |user_ids| {
+        let db = db.clone();
+        async move { repo::find_users_by_ids(&db, user_ids).await }
+    })
+}
+
+fn spawn<F, Fut>(
+    hub: &Hub,
+    user_ids: Vec<Uuid>,
+    find: F,
+) -> mpsc::Receiver<Result<repo::user::Model, AppError>>
+where
+    F: Fn(Vec<Uuid>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Vec<repo::user::Model>, Error>> + Send,
+{
+    let (sender, receiver) = mpsc::channel(user_ids.len().max(1));
+    // Subscribed before the first read, so a change in between is sent twice rather than never.
+    let mut changes = hub.subscribe();
+
+    tokio::spawn(async move {
+        let watched: HashSet<Uuid> = user_ids.iter().copied().collect();
+        let mut pending = user_ids.clone();
+
+        loop {
+            if send(&find, &sender, pending).await.is_err() {
+                return;
+            }
+
+            pending = loop {
+                let change = tokio::select! {
+                    _ = sender.closed() => return,
+                    change = changes.recv() => change,
+                };
+
+                match change {
+                    Ok(user_id) if watched.contains(&user_id) => break vec![user_id],
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => break user_ids.clone(),
+                    Err(RecvError::Closed) => return,
+                }
+            };
+        }
+    });
+
+    receiver
+}
+
+// Errs once the stream is gone, either closed by the client or ended after a failed read.
+async fn send<F, Fut>(
+    find: &F,
+    sender: &mpsc::Sender<Result<repo::user::Model, AppError>>,
+    user_ids: Vec<Uuid>,
+) -> Result<(), ()>
+where
+    F: Fn(Vec<Uuid>) -> Fut,
+    Fut: Future<Output = Result<Vec<repo::user::Model>, Error>>,
+{
+    match find(user_ids).await {
+        Ok(users) => {
+            for user in users {
+                sender.send(Ok(user)).await.map_err(|_| ())?;
+            }
+
+            Ok(())
+        }
+        Err(err) => {
+            let _ = sender.send(Err(err.into())).await;
+
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::Error;
+    use chrono::Utc;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use crate::app::{error::AppError, users::repo};
+
+    use super::{spawn, Hub};
+
+    type Calls = Arc<Mutex<Vec<Vec<Uuid>>>>;
+
+    fn user(id: Uuid) -> repo::user::Model {
+        repo::user::Model {
+            id,
+            first_name: "First".into(),
+            last_name: "Last".into(),
+            locale: None,
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: None,
+            status: repo::user::Status::Active,
+            status_reason: None,
+            status_until: None,
+            handle: None,
+            handle_key: None,
+            handle_changed_at: None,
+            preferred_color: None,
+            picture_id: None,
+            picture_url: None,
+            timezone: None,
+        }
+    }
+
+    // Nothing runs until the test first awaits, so everything published before that is already
+    // queued when the watch task starts.
+    fn watch(
+        hub: &Hub,
+        user_ids: Vec<Uuid>,
+    ) -> (mpsc::Receiver<Result<repo::user::Model, AppError>>, Calls) {
+        let calls = Calls::default();
+        let receiver = spawn(hub, user_ids, {
+            let calls = calls.clone();
+            move |user_ids: Vec<Uuid>| {
+                calls.lock().unwrap().push(user_ids.clone());
+                async move { Ok::<_, Error>(user_ids.into_iter().map(user).collect()) }
+            }
+        });
+
+        (receiver, calls)
+    }
+
+    async fn next(receiver: &mut mpsc::Receiver<Result<repo::user::Model, AppError>>) -> Uuid {
+        receiver.recv().await.unwrap().unwrap().id
+    }
+
+    #[tokio::test]
+    async fn should_fan_out_to_every_watcher() {
+        let (a, b) = (Uuid::now_v7(), Uuid::now_v7());
+        let hub = Hub::new(16);
+        let (mut first, _) = watch(&hub, vec![a]);
+        let (mut second, _) = watch(&hub, vec![a, b]);
+
+        hub.publish(a);
+
+        assert_eq!(next(&mut first).await, a);
+        assert_eq!(next(&mut first).await, a);
+        assert_eq!(next(&mut second).await, a);
+        assert_eq!(next(&mut second).await, b);
+        assert_eq!(next(&mut second).await, a);
+    }
+
+    #[tokio::test]
+    async fn should_only_send_watched_users() {
+        let (a, b) = (Uuid::now_v7(), Uuid::now_v7());
+        let hub = Hub::new(16);
+        let (mut receiver, calls) = watch(&hub, vec![a]);
+
+        hub.publish(b);
+        hub.publish(a);
+
+        assert_eq!(next(&mut receiver).await, a);
+        assert_eq!(next(&mut receiver).await, a);
+        assert_eq!(*calls.lock().unwrap(), vec![vec![a], vec![a]]);
+    }
+
+    #[tokio::test]
+    async fn should_resend_all_after_lagging() {
+        let (a, b) = (Uuid::now_v7(), Uuid::now_v7());
+        let hub = Hub::new(1);
+        let (mut receiver, calls) = watch(&hub, vec![a, b]);
+
+        for _ in 0..3 {
+            hub.publish(Uuid::now_v7());
+        }
+
+        assert_eq!(next(&mut receiver).await, a);
+        assert_eq!(next(&mut receiver).await, b);
+        assert_eq!(next(&mut receiver).await, a);
+        assert_eq!(next(&mut receiver).await, b);
+        assert_eq!(*calls.lock().unwrap(), vec![vec![a, b], vec![a, b]]);
+    }
+
+    #[tokio::test]
+    async fn should_clamp_zero_capacity() {
+        let a = Uuid::now_v7();
+        let hub = Hub::new(0);
+        let (mut receiver, _) = watch(&hub, vec![a]);
+
+        hub.publish(a);
+
+        assert_eq!(next(&mut receiver).await, a);
+        assert_eq!(next(&mut receiver).await, a);
+    }
+}